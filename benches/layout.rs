@@ -0,0 +1,127 @@
+//! Measures the cost of laying out large panels every frame, from scratch and with a
+//! `LayoutCache`. With the cache, each frame's panel is reconciled into the one kept from
+//! the previous frame first, like `UiHost` does. Reconciling compares every widget, so it is
+//! timed on its own.
+//! Run with `cargo bench --bench layout`.
+
+use grui::headless::load_font;
+use grui::layout::{calculate_ui_layout, LayoutCache, Size};
+use grui::render::TextMeasure;
+use grui::software::SoftwareRenderer;
+use grui::{UiNode, Widget};
+
+use glam::vec2;
+
+use std::time::{Duration, Instant};
+
+const FRAMES: u32 = 50;
+
+/// A tool panel of `rows` property rows, each a label, a value and a button. `changed` is a
+/// row showing a different value.
+fn panel(rows: usize, changed: Option<(usize, usize)>) -> UiNode {
+    let mut root = UiNode::new(Widget::Vertical);
+    let mut list = UiNode::new(Widget::Scroll);
+    list.props.shrink = 1.0;
+    list.props.gap = 2.0;
+
+    for i in 0..rows {
+        let mut row = UiNode::new(Widget::Horizontal);
+        row.props.gap = 4.0;
+        let mut name = UiNode::new(Widget::Label(format!("Property {}", i)));
+        name.props.width = Some(Size::Percent(40.0));
+        row.push(name);
+        let value = match changed {
+            Some((row, value)) if row == i => value,
+            _ => i * 7,
+        };
+        let mut value = UiNode::new(Widget::Label(value.to_string()));
+        value.props.grow = 1.0;
+        row.push(value);
+        row.push(Widget::Button("Reset".to_owned()));
+        list.push(row);
+    }
+
+    root.push(list);
+    root
+}
+
+/// Reconciles each of `frames` into `tree` and lays it out, and returns the time spent on
+/// each of the two
+fn cached(
+    tree: &mut UiNode,
+    cache: &mut LayoutCache,
+    text: &mut dyn TextMeasure,
+    frames: Vec<UiNode>,
+) -> (Duration, Duration) {
+    let size = vec2(800.0, 600.0);
+    let mut layout = Duration::default();
+    let mut reconcile = Duration::default();
+
+    for frame in frames {
+        let start = Instant::now();
+        tree.reconcile(frame);
+        reconcile += start.elapsed();
+
+        let start = Instant::now();
+        cache.layout(tree, text, size);
+        layout += start.elapsed();
+    }
+
+    (layout, reconcile)
+}
+
+fn count_nodes(node: &UiNode) -> usize {
+    1 + node
+        .children
+        .iter()
+        .map(|(_, child)| count_nodes(child))
+        .sum::<usize>()
+}
+
+fn per_frame(elapsed: Duration) -> String {
+    format!("{:>9.1}us", elapsed.as_secs_f64() * 1e6 / f64::from(FRAMES))
+}
+
+fn main() {
+    let font = load_font().expect("couldn't load the font");
+    let mut text = SoftwareRenderer::new(1, 1, font).unwrap();
+    let size = vec2(800.0, 600.0);
+
+    println!(
+        "{:>7} {:>11} {:>11} {:>11} {:>11} {:>26}",
+        "nodes", "scratch", "unchanged", "one label", "reconcile", "visited/measured/arranged"
+    );
+
+    for &rows in &[25, 250, 2500] {
+        let root = panel(rows, None);
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            calculate_ui_layout(&root, &mut text, size);
+        }
+        let scratch = start.elapsed();
+
+        let mut tree = UiNode::new(Widget::Vertical);
+        let mut cache = LayoutCache::new();
+        cached(&mut tree, &mut cache, &mut text, vec![root]);
+
+        let frames = (0..FRAMES).map(|_| panel(rows, None)).collect();
+        let (unchanged, _) = cached(&mut tree, &mut cache, &mut text, frames);
+
+        let frames = (0..FRAMES as usize)
+            .map(|frame| panel(rows, Some((frame % rows, frame))))
+            .collect();
+        let (one_label, reconcile) = cached(&mut tree, &mut cache, &mut text, frames);
+        let stats = cache.stats();
+
+        println!(
+            "{:>7} {} {} {} {} {:>26}",
+            count_nodes(&tree),
+            per_frame(scratch),
+            per_frame(unchanged),
+            per_frame(one_label),
+            per_frame(reconcile),
+            format!("{}/{}/{}", stats.visited, stats.measured, stats.arranged),
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! The prototype's window: shows a .grui file, reloading it when it changes.
+//! Press F12 to print the current UI tree as .grui source.
+
+use grui::ast;
+use grui::loader::UiLoader;
+use grui::registry::WidgetRegistry;
+use grui::render::NanovgRenderer;
+use grui::serialize::serialize_ui;
+use grui::{FrameSource, InputEvent, Ui, UiHost, UiResult, FONT_PATH, INIT_WINDOW_SIZE};
+
+use glam::vec2;
+use glutin::GlContext;
+use nanovg::Font;
+
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Pixels scrolled per line, for wheels which report lines
+const WHEEL_LINE_HEIGHT: f32 = 40.0;
+
+fn main() {
+    let mut events_loop = glutin::EventsLoop::new();
+    let window = glutin::WindowBuilder::new()
+        .with_title("gui proto")
+        .with_dimensions(INIT_WINDOW_SIZE.0, INIT_WINDOW_SIZE.1);
+    let context = glutin::ContextBuilder::new()
+        .with_vsync(false)
+        .with_multisampling(4)
+        .with_srgb(true);
+    let gl_window = glutin::GlWindow::new(window, context, &events_loop).unwrap();
+
+    unsafe {
+        gl_window.make_current().unwrap();
+        gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
+    }
+
+    let context = nanovg::ContextBuilder::new()
+        .stencil_strokes()
+        .build()
+        .expect("Initialization of NanoVG failed!");
+
+    let font = Font::from_file(&context, "Roboto-Regular", FONT_PATH)
+        .expect("Failed to load font 'Roboto-Regular.ttf'");
+
+    let mut running = true;
+    // Set by pressing F12, to print the emitted UI as .grui source
+    let mut dump_ui = false;
+    let mut host = UiHost::new(vec2(INIT_WINDOW_SIZE.0 as f32, INIT_WINDOW_SIZE.1 as f32));
+
+    // `demo [file.grui]`, or `demo -` to read the UI from stdin
+    let mut ui_loader = match std::env::args().nth(1) {
+        Some(ref arg) if arg == "-" => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .expect("Failed to read stdin");
+            UiLoader::from_string("<stdin>", text)
+        }
+        Some(path) => UiLoader::new(path),
+        None => UiLoader::new("hello.grui"),
+    };
+    let widget_registry = WidgetRegistry::default();
+    let mut ui_model: HashMap<String, ast::Value> = HashMap::new();
+    for (i, name) in ["Sword", "Shield", "Potion"].iter().enumerate() {
+        ui_model.insert(
+            format!("inventory.{}.name", i),
+            ast::Value::String(name.to_string()),
+        );
+    }
+
+    loop {
+        events_loop.poll_events(|event| {
+            if let glutin::Event::WindowEvent { event, .. } = event {
+                match event {
+                    glutin::WindowEvent::Closed => running = false,
+                    glutin::WindowEvent::Resized(w, h) => gl_window.resize(w, h),
+                    glutin::WindowEvent::CursorMoved { position, .. } => host.handle_event(
+                        InputEvent::MouseMove(vec2(position.0 as f32, position.1 as f32)),
+                    ),
+                    glutin::WindowEvent::MouseInput { state, .. } => {
+                        host.handle_event(match state {
+                            glutin::ElementState::Pressed => InputEvent::MouseDown,
+                            glutin::ElementState::Released => InputEvent::MouseUp,
+                        })
+                    }
+                    glutin::WindowEvent::MouseWheel { delta, .. } => {
+                        host.handle_event(InputEvent::Wheel(match delta {
+                            glutin::MouseScrollDelta::LineDelta(x, y) => {
+                                vec2(x, y) * WHEEL_LINE_HEIGHT
+                            }
+                            glutin::MouseScrollDelta::PixelDelta(x, y) => vec2(x, y),
+                        }))
+                    }
+                    glutin::WindowEvent::KeyboardInput {
+                        input:
+                            glutin::KeyboardInput {
+                                state: glutin::ElementState::Pressed,
+                                virtual_keycode: Some(glutin::VirtualKeyCode::F12),
+                                ..
+                            },
+                        ..
+                    } => dump_ui = true,
+                    _ => {}
+                }
+            }
+        });
+
+        if !running {
+            break;
+        }
+
+        let (width, height) = gl_window.get_inner_size().unwrap();
+        let (width, height) = (width as i32, height as i32);
+
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+            gl::ClearColor(0.3, 0.3, 0.32, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+
+        let (width, height) = (width as f32, height as f32);
+        host.handle_event(InputEvent::Resize(vec2(width, height)));
+
+        context.frame((width, height), gl_window.hidpi_factor(), |frame| {
+            match host.interaction_state().hover_widget() {
+                Some(uid) => ui_model.insert(
+                    "debug.hover".to_owned(),
+                    ast::Value::String(format!("{:?}", uid)),
+                ),
+                None => ui_model.remove("debug.hover"),
+            };
+
+            if ui_loader.poll(&widget_registry) {
+                for diagnostic in ui_loader.diagnostics() {
+                    eprintln!("{}", diagnostic);
+                }
+            }
+
+            let source = FrameSource {
+                ast: ui_loader.ast(),
+                diagnostics: ui_loader.diagnostics(),
+                registry: &widget_registry,
+                model: &ui_model,
+            };
+
+            let mut renderer = NanovgRenderer::new(&frame, font);
+
+            host.frame(&mut renderer, &source, &mut |ui| {
+                let _ = do_ui_stuff(ui);
+
+                if dump_ui {
+                    print!("{}", serialize_ui(ui.node()));
+                    dump_ui = false;
+                }
+            });
+        });
+
+        gl_window.swap_buffers().unwrap();
+    }
+}
+
+// ----
+
+fn do_ui_stuff(ui: &mut Ui) -> UiResult<()> {
+    if ui.button("I'm from code").clicked() {
+        println!("code button clicked!");
+    }
+
+    if ui.id("special_button")?.clicked() {
+        println!("special button clicked!");
+    }
+
+    let mut append_box = ui.id("append_box")?;
+    append_box.label("label 1");
+    append_box.label("label 2");
+    append_box.label("label 3");
+
+    Ok(())
+}
@@ -1,14 +1,227 @@
+#[derive(Debug, Clone)]
+pub enum Node {
+    Item(Item),
+    /// `include "path/to/file.grui";` Expanded by the loader, so never seen during emission.
+    Include(Include),
+    /// `template name(params...) { ... }` Expanded by the loader, so never seen during emission.
+    Template(Template),
+    /// `if {flag} { ... } else { ... }` Evaluated against the data model during emission.
+    If(If),
+    /// `for item in {list} { ... }` Evaluated against the data model during emission.
+    For(For),
+}
+
+#[derive(Debug, Clone)]
+pub struct Include {
+    pub path: String,
+    pub span: Span,
+}
+
+/// A reusable block of items. Instantiated like a widget, with positional values
+/// substituted for the parameter identifiers used in the body.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Node>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct If {
+    /// Path of the binding which selects the branch
+    pub condition: String,
+    pub body: Vec<Node>,
+    /// `else if` chains are stored as a single nested `If` node
+    pub else_body: Option<Vec<Node>>,
+    pub span: Span,
+    /// Spans of the template instantiations this block was expanded from, innermost first.
+    /// Copies of a template body share their spans, so these tell them apart.
+    pub instances: Vec<Span>,
+}
+
+/// Repeats its body once for every key of the bound list, with `var` standing for the element.
+#[derive(Debug, Clone)]
+pub struct For {
+    pub var: String,
+    /// Path of the binding to iterate
+    pub list: String,
+    pub body: Vec<Node>,
+    pub span: Span,
+    /// See `If::instances`
+    pub instances: Vec<Span>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Item {
     pub uid: Option<String>,
     pub ident: String,
+    /// Positional values. Widgets take at most one, templates one per parameter.
+    pub values: Vec<Value>,
+    pub properties: Properties,
+    /// `None` for items terminated with `;`, `Some` for ones followed by a `{ ... }` block.
+    pub children: Option<Vec<Node>>,
+    pub span: Span,
+}
+
+impl Item {
+    /// The positional value of widgets which take a single one
+    pub fn value(&self) -> Option<&Value> {
+        self.values.first()
+    }
+}
+
+/// Index of a source file in a `SourceMap`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FileId(pub usize);
+
+/// Byte range in the source text of a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Property {
+    pub name: String,
     pub value: Value,
+    pub span: Span,
+}
+
+/// Named attributes of an item, such as `(width: 240, enabled: false)`. Keeps declaration order.
+#[derive(Debug, Clone, Default)]
+pub struct Properties(pub Vec<Property>);
+
+impl Properties {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.iter().find(|p| p.name == name).map(|p| &p.value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    List(Vec<Item>),
     Int(i32),
+    Float(f32),
+    Bool(bool),
+    Color(Color),
+    Dimension(f32, Unit),
     Ident(String),
     String(String),
+    /// `{player.health}`, looked up in the application's data model during emission
+    Binding(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Px,
+    Percent,
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Px => write!(f, "px"),
+            Unit::Percent => write!(f, "%"),
+        }
+    }
+}
+
+impl Color {
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Parses `rgb`, `rgba`, `rrggbb` or `rrggbbaa` hex digits (without the leading `#`)
+    pub fn from_hex(s: &str) -> Option<Self> {
+        if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let digit = |i: usize| u8::from_str_radix(&s[i..=i], 16).unwrap();
+        let byte = |i: usize| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap();
+
+        match s.len() {
+            3 | 4 => Some(Self {
+                r: digit(0) * 17,
+                g: digit(1) * 17,
+                b: digit(2) * 17,
+                a: if s.len() == 4 { digit(3) * 17 } else { 255 },
+            }),
+            6 | 8 => Some(Self {
+                r: byte(0),
+                g: byte(1),
+                b: byte(2),
+                a: if s.len() == 8 { byte(3) } else { 255 },
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_ident(&self) -> Option<&str> {
+        match self {
+            Value::Ident(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Numeric value of ints, floats and pixel dimensions.
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Value::Int(v) => Some(v as f32),
+            Value::Float(v) => Some(v),
+            Value::Dimension(v, Unit::Px) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name of the value's type, for diagnostics
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "boolean",
+            Value::Color(_) => "color",
+            Value::Dimension(..) => "dimension",
+            Value::Ident(_) => "identifier",
+            Value::String(_) => "string",
+            Value::Binding(_) => "binding",
+        }
+    }
+
+    pub fn as_color(&self) -> Option<Color> {
+        match *self {
+            Value::Color(c) => Some(c),
+            _ => None,
+        }
+    }
 }
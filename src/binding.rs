@@ -0,0 +1,177 @@
+use crate::ast;
+use crate::registry::{ValueKind, WidgetSignature};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Application state which .grui values can bind to with `{path.to.value}`.
+/// Bindings are looked up anew every time the UI is emitted.
+pub trait DataModel {
+    /// Current value at a dotted path such as `player.health`, if there is one.
+    fn get(&self, path: &str) -> Option<ast::Value>;
+
+    /// Keys of the elements of the list at `path`, in display order, for `for` blocks.
+    /// Element values are then looked up at `path.key.field`.
+    ///
+    /// Widgets created for an element are identified by its key, so keys should stay
+    /// the same when other elements are added or removed.
+    fn list_keys(&self, _path: &str) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A flat store keyed by the full dotted path. The keys of a list are the distinct path
+/// segments following it, sorted numerically where possible, so `players.0.name` and
+/// `players.1.name` make up a two-element list at `players`.
+impl DataModel for HashMap<String, ast::Value> {
+    fn get(&self, path: &str) -> Option<ast::Value> {
+        HashMap::get(self, path).cloned()
+    }
+
+    fn list_keys(&self, path: &str) -> Vec<String> {
+        let prefix = format!("{}.", path);
+
+        let mut keys: Vec<&str> = HashMap::keys(self)
+            .filter_map(|k| k.strip_prefix(&prefix))
+            .map(|rest| rest.split('.').next().unwrap())
+            .collect();
+
+        keys.sort_by(|a, b| match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        });
+        keys.dedup();
+
+        keys.into_iter().map(str::to_owned).collect()
+    }
+}
+
+/// A model without any values.
+impl DataModel for () {
+    fn get(&self, _path: &str) -> Option<ast::Value> {
+        None
+    }
+}
+
+/// The model seen inside a `for` block, where paths starting with the loop variable refer
+/// to the current element of the list.
+pub struct ScopedModel<'a> {
+    pub parent: &'a dyn DataModel,
+    pub var: &'a str,
+    /// Full path of the current element, such as `players.3`
+    pub element: String,
+}
+
+impl<'a> ScopedModel<'a> {
+    fn resolve(&self, path: &str) -> Option<String> {
+        let rest = path.strip_prefix(self.var)?;
+
+        if rest.is_empty() || rest.starts_with('.') {
+            Some(format!("{}{}", self.element, rest))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> DataModel for ScopedModel<'a> {
+    fn get(&self, path: &str) -> Option<ast::Value> {
+        match self.resolve(path) {
+            Some(path) => self.parent.get(&path),
+            None => self.parent.get(path),
+        }
+    }
+
+    fn list_keys(&self, path: &str) -> Vec<String> {
+        match self.resolve(path) {
+            Some(path) => self.parent.list_keys(&path),
+            None => self.parent.list_keys(path),
+        }
+    }
+}
+
+/// Whether the binding at `path` selects the body of an `if` block. Missing values, `false`,
+/// zero and empty strings select the `else` branch.
+pub fn is_truthy(model: &dyn DataModel, path: &str) -> bool {
+    match model.get(path) {
+        None => false,
+        Some(ast::Value::Bool(v)) => v,
+        Some(ast::Value::Int(v)) => v != 0,
+        Some(ast::Value::Float(v)) => v != 0.0,
+        Some(ast::Value::String(s)) => !s.is_empty(),
+        Some(_) => true,
+    }
+}
+
+/// Replaces bindings in the values and properties of `item` with their current values from
+/// `model`. Items without bindings are returned as they are.
+///
+/// Bound values are converted to text where the widget expects a string, so that numbers can
+/// be shown in labels. Missing string values show the binding itself, to make them easy to
+/// spot; other missing values are dropped, leaving the widget's default in place.
+pub fn resolve_bindings<'a>(
+    item: &'a ast::Item,
+    signature: &WidgetSignature,
+    model: &dyn DataModel,
+) -> Cow<'a, ast::Item> {
+    let is_binding = |v: &ast::Value| matches!(v, ast::Value::Binding(_));
+
+    if !item.values.iter().any(is_binding)
+        && !item.properties.0.iter().any(|p| is_binding(&p.value))
+    {
+        return Cow::Borrowed(item);
+    }
+
+    let mut item = item.clone();
+
+    item.values = item
+        .values
+        .into_iter()
+        .filter_map(|value| resolve_value(value, signature.value, model))
+        .collect();
+
+    item.properties.0 = item
+        .properties
+        .0
+        .into_iter()
+        .filter_map(|mut prop| {
+            let kind = signature.property_kind(&prop.name);
+            prop.value = resolve_value(prop.value, kind, model)?;
+            Some(prop)
+        })
+        .collect();
+
+    Cow::Owned(item)
+}
+
+fn resolve_value(
+    value: ast::Value,
+    kind: Option<ValueKind>,
+    model: &dyn DataModel,
+) -> Option<ast::Value> {
+    let path = match value {
+        ast::Value::Binding(path) => path,
+        value => return Some(value),
+    };
+
+    match (model.get(&path), kind) {
+        (Some(ast::Value::String(s)), _) => Some(ast::Value::String(s)),
+        (Some(value), Some(ValueKind::String)) => Some(ast::Value::String(display_text(&value))),
+        (Some(value), _) => Some(value),
+        (None, Some(ValueKind::String)) => Some(ast::Value::String(format!("{{{}}}", path))),
+        (None, _) => None,
+    }
+}
+
+/// Plain text of a value, as shown in widgets
+fn display_text(value: &ast::Value) -> String {
+    match value {
+        ast::Value::Int(v) => v.to_string(),
+        ast::Value::Float(v) => v.to_string(),
+        ast::Value::Bool(v) => v.to_string(),
+        ast::Value::Color(c) => format!("#{:02x}{:02x}{:02x}{:02x}", c.r, c.g, c.b, c.a),
+        ast::Value::Dimension(v, unit) => format!("{}{}", v, unit),
+        ast::Value::Ident(s) | ast::Value::String(s) => s.clone(),
+        ast::Value::Binding(path) => format!("{{{}}}", path),
+    }
+}
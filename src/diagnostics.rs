@@ -0,0 +1,260 @@
+use crate::ast::{FileId, Span, Unit};
+use crate::lexer::{Lexer, Tok};
+use crate::syntax::{self, ParseError};
+use std::error::Error;
+use std::path::PathBuf;
+
+/// A named chunk of .grui source text, used to resolve byte offsets into line/column info.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub name: String,
+    pub text: String,
+    /// The file the text was read from, or `None` for in-memory sources
+    pub path: Option<PathBuf>,
+}
+
+impl Source {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            text: text.into(),
+            path: None,
+        }
+    }
+
+    pub fn location(&self, offset: usize) -> SourceLocation {
+        let offset = offset.min(self.text.len());
+        let line_start = self.text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = self.text[offset..]
+            .find('\n')
+            .map(|i| offset + i)
+            .unwrap_or_else(|| self.text.len());
+
+        SourceLocation {
+            line: self.text[..line_start].matches('\n').count() + 1,
+            column: self.text[line_start..offset].chars().count() + 1,
+            source_line: self.text[line_start..line_end].trim_end().to_owned(),
+        }
+    }
+}
+
+/// All the sources which make up a UI, such as a .grui file and the files it includes.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    sources: Vec<Source>,
+}
+
+impl SourceMap {
+    pub fn add(&mut self, source: Source) -> FileId {
+        self.sources.push(source);
+        FileId(self.sources.len() - 1)
+    }
+
+    pub fn get(&self, file: FileId) -> &Source {
+        &self.sources[file.0]
+    }
+
+    /// Diagnostic pointing at the start of `span`
+    pub fn diagnostic(&self, span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.get(span.file), span.start, message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, in characters
+    pub column: usize,
+    /// The full text of the line containing the location
+    pub source_line: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub location: Option<SourceLocation>,
+    pub message: String,
+    pub expected: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(source: &Source, offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            file: source.name.clone(),
+            location: Some(source.location(offset)),
+            message: message.into(),
+            expected: Vec::new(),
+        }
+    }
+
+    /// A diagnostic which does not point at any particular place in the file,
+    /// such as an I/O error.
+    pub fn whole_file(file: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            file: file.into(),
+            location: None,
+            message: message.into(),
+            expected: Vec::new(),
+        }
+    }
+
+    pub fn from_parse_error(source: &Source, err: &ParseError) -> Self {
+        match err {
+            ParseError::InvalidToken { location } => Self::new(source, *location, "invalid token"),
+            ParseError::UnrecognizedEOF { location, expected } => {
+                let mut res = Self::new(source, *location, "unexpected end of file");
+                res.expected = valid_expected(source, *location, expected);
+                res
+            }
+            ParseError::UnrecognizedToken {
+                token: (start, tok, _),
+                expected,
+            } => {
+                let mut res = Self::new(source, *start, format!("unexpected token `{}`", tok));
+                res.expected = valid_expected(source, *start, expected);
+                res
+            }
+            ParseError::ExtraToken {
+                token: (start, tok, _),
+            } => Self::new(source, *start, format!("extra token `{}`", tok)),
+            ParseError::User { error } => Self::new(source, error.location, error.to_string()),
+        }
+    }
+
+    /// Compact single-line form: `file:line:col: message`
+    pub fn headline(&self) -> String {
+        match self.location {
+            Some(ref loc) => format!(
+                "{}:{}:{}: {}",
+                self.file, loc.line, loc.column, self.message
+            ),
+            None => format!("{}: {}", self.file, self.message),
+        }
+    }
+
+    pub fn expected_list(&self) -> Option<String> {
+        if self.expected.is_empty() {
+            None
+        } else {
+            Some(format!("expected one of: {}", self.expected.join(", ")))
+        }
+    }
+}
+
+/// A token of the kind LALRPOP names `name` in its list of expected tokens
+fn example_token(name: &str) -> Option<Tok<'static>> {
+    Some(match name.trim_matches('"') {
+        "{" => Tok::LBrace,
+        "}" => Tok::RBrace,
+        "(" => Tok::LParen,
+        ")" => Tok::RParen,
+        ":" => Tok::Colon,
+        "," => Tok::Comma,
+        ";" => Tok::Semi,
+        "#" => Tok::Hash("name"),
+        "true" => Tok::True,
+        "false" => Tok::False,
+        "include" => Tok::Include,
+        "template" => Tok::Template,
+        "if" => Tok::If,
+        "else" => Tok::Else,
+        "for" => Tok::For,
+        "in" => Tok::In,
+        "binding" => Tok::Binding("value"),
+        "ident" => Tok::Ident("name"),
+        "int" => Tok::Int(0),
+        "float" => Tok::Float(0.0),
+        "dimension" => Tok::Dimension(0.0, Unit::Px),
+        "string" => Tok::String(String::new()),
+        _ => return None,
+    })
+}
+
+/// LALRPOP merges similar parser states, so the tokens it expects at an error include some
+/// which are only valid in other contexts. Keeps the ones the parser actually accepts after
+/// the tokens before `location`.
+fn valid_expected(source: &Source, location: usize, expected: &[String]) -> Vec<String> {
+    let before: Vec<_> = Lexer::new(&source.text)
+        .take_while(|tok| matches!(tok, Ok((start, _, _)) if *start < location))
+        .collect();
+
+    expected
+        .iter()
+        .filter(|name| {
+            let tok = match example_token(name) {
+                Some(tok) => tok,
+                None => return true,
+            };
+            let tokens = before
+                .iter()
+                .cloned()
+                .chain(std::iter::once(Ok((location, tok, location))));
+
+            // Invalid tokens are rejected right where they are, and valid ones only fail
+            // later on, at the end of the file
+            !matches!(
+                syntax::parse(FileId(0), tokens),
+                Err(ParseError::UnrecognizedToken { token: (start, _, _), .. }) if start == location
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "error: {}", self.message)?;
+
+        match self.location {
+            Some(ref loc) => {
+                let line_no = loc.line.to_string();
+                let gutter = " ".repeat(line_no.len());
+
+                writeln!(f, "{}--> {}:{}:{}", gutter, self.file, loc.line, loc.column)?;
+                writeln!(f, "{} |", gutter)?;
+                writeln!(f, "{} | {}", line_no, loc.source_line)?;
+
+                // Keep tabs so the caret lines up with the source line
+                let pad: String = loc
+                    .source_line
+                    .chars()
+                    .take(loc.column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                write!(f, "{} | {}^", gutter, pad)?;
+
+                if let Some(expected) = self.expected_list() {
+                    write!(f, "\n{} = {}", gutter, expected)?;
+                }
+            }
+            None => write!(f, " --> {}", self.file)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for Diagnostic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::parse_ui_source;
+
+    fn expected(text: &str) -> Vec<String> {
+        let source = Source::new("test.grui", text);
+        parse_ui_source(&source, FileId(0)).unwrap_err().expected
+    }
+
+    #[test]
+    fn expected_tokens_fit_the_context() {
+        let after_value = expected("label \"x\"");
+        assert!(after_value.contains(&"\";\"".to_owned()));
+        assert!(after_value.contains(&"\"(\"".to_owned()));
+        assert!(!after_value.contains(&"\")\"".to_owned()));
+        assert!(!after_value.contains(&"\",\"".to_owned()));
+
+        assert_eq!(expected("label \"x\" (width 3);"), vec!["\":\""]);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::ast::Color;
+use crate::render::{Fill, Rect, Renderer, TextMeasure, TextMetrics};
+use crate::WidgetUid;
+
+use glam::Vec2;
+
+use std::fmt;
+
+/// A single shape or run of text, positioned in window coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Rect {
+        pos: Vec2,
+        size: Vec2,
+        fill: Fill,
+    },
+    RoundedRect {
+        pos: Vec2,
+        size: Vec2,
+        radius: f32,
+        fill: Fill,
+    },
+    StrokeRoundedRect {
+        pos: Vec2,
+        size: Vec2,
+        radius: f32,
+        color: Color,
+    },
+    Text {
+        pos: Vec2,
+        text: String,
+        size: f32,
+        color: Color,
+    },
+}
+
+impl Primitive {
+    pub fn draw(&self, r: &mut dyn Renderer) {
+        match *self {
+            Primitive::Rect { pos, size, fill } => r.fill_rect(pos, size, fill),
+            Primitive::RoundedRect {
+                pos,
+                size,
+                radius,
+                fill,
+            } => r.fill_rounded_rect(pos, size, radius, fill),
+            Primitive::StrokeRoundedRect {
+                pos,
+                size,
+                radius,
+                color,
+            } => r.stroke_rounded_rect(pos, size, radius, color),
+            Primitive::Text {
+                pos,
+                ref text,
+                size,
+                color,
+            } => r.text(pos, text, size, color),
+        }
+    }
+}
+
+fn write_color(f: &mut fmt::Formatter, c: Color) -> fmt::Result {
+    write!(f, "#{:02x}{:02x}{:02x}{:02x}", c.r, c.g, c.b, c.a)
+}
+
+pub(crate) fn write_rect(f: &mut fmt::Formatter, pos: Vec2, size: Vec2) -> fmt::Result {
+    write!(f, "{} {} {} {}", pos.x(), pos.y(), size.x(), size.y())
+}
+
+fn write_fill(f: &mut fmt::Formatter, fill: Fill) -> fmt::Result {
+    match fill {
+        Fill::Solid(color) => write_color(f, color),
+        Fill::LinearGradient {
+            start,
+            end,
+            start_color,
+            end_color,
+        } => {
+            write!(f, "gradient ({}, {}) ", start.x(), start.y())?;
+            write_color(f, start_color)?;
+            write!(f, " -> ({}, {}) ", end.x(), end.y())?;
+            write_color(f, end_color)
+        }
+    }
+}
+
+/// A compact single-line form, meant for reading and diffing
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Primitive::Rect { pos, size, fill } => {
+                write!(f, "fill_rect ")?;
+                write_rect(f, *pos, *size)?;
+                write!(f, " ")?;
+                write_fill(f, *fill)
+            }
+            Primitive::RoundedRect {
+                pos,
+                size,
+                radius,
+                fill,
+            } => {
+                write!(f, "fill_rounded_rect ")?;
+                write_rect(f, *pos, *size)?;
+                write!(f, " r={} ", radius)?;
+                write_fill(f, *fill)
+            }
+            Primitive::StrokeRoundedRect {
+                pos,
+                size,
+                radius,
+                color,
+            } => {
+                write!(f, "stroke_rounded_rect ")?;
+                write_rect(f, *pos, *size)?;
+                write!(f, " r={} ", radius)?;
+                write_color(f, *color)
+            }
+            Primitive::Text {
+                pos,
+                text,
+                size,
+                color,
+            } => {
+                write!(f, "text {} {} {:?} size={} ", pos.x(), pos.y(), text, size)?;
+                write_color(f, *color)
+            }
+        }
+    }
+}
+
+/// A primitive, with what it belongs to and where it may draw
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayItem {
+    pub primitive: Primitive,
+    /// The widget the primitive was drawn for, if any
+    pub widget: Option<WidgetUid>,
+    /// Drawing is limited to this rectangle, if set
+    pub clip: Option<Rect>,
+    /// Items with a higher z are drawn on top, regardless of their order in the frame pass
+    pub z: i32,
+}
+
+/// Everything a frame of the UI draws, in drawing order, so that it can be inspected,
+/// kept around, or drawn by any `Renderer`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayList {
+    items: Vec<DisplayItem>,
+}
+
+impl DisplayList {
+    /// Items ordered by z, and then by the order they were added in
+    pub fn items(&self) -> &[DisplayItem] {
+        &self.items
+    }
+
+    /// Items drawn for the given widget, not including its children
+    pub fn widget_items<'a>(
+        &'a self,
+        widget: &'a WidgetUid,
+    ) -> impl Iterator<Item = &'a DisplayItem> + 'a {
+        self.items
+            .iter()
+            .filter(move |item| item.widget.as_ref() == Some(widget))
+    }
+
+    /// Draws every item, and leaves the renderer's clip as it was.
+    pub fn draw(&self, r: &mut dyn Renderer) {
+        let initial_clip = r.clip();
+        let mut clip = initial_clip;
+
+        for item in &self.items {
+            let item_clip = match (initial_clip, item.clip) {
+                (Some(initial), Some(item)) => Some(initial.intersect(&item)),
+                (initial, item) => item.or(initial),
+            };
+
+            if item_clip != clip {
+                r.set_clip(item_clip);
+                clip = item_clip;
+            }
+
+            item.primitive.draw(r);
+        }
+
+        if clip != initial_clip {
+            r.set_clip(initial_clip);
+        }
+    }
+}
+
+/// Collects the drawing of a frame pass into a `DisplayList`. Text is measured by another
+/// renderer, normally the one the list will be drawn with.
+pub(crate) struct DisplayListBuilder<'a> {
+    measure: &'a mut dyn Renderer,
+    items: Vec<DisplayItem>,
+    clip: Option<Rect>,
+    /// Set on the items added from now on
+    pub(crate) widget: Option<WidgetUid>,
+    pub(crate) z: i32,
+}
+
+impl<'a> DisplayListBuilder<'a> {
+    pub(crate) fn new(measure: &'a mut dyn Renderer) -> Self {
+        Self {
+            measure,
+            items: Vec::new(),
+            clip: None,
+            widget: None,
+            z: 0,
+        }
+    }
+
+    fn push(&mut self, primitive: Primitive) {
+        self.items.push(DisplayItem {
+            primitive,
+            widget: self.widget.clone(),
+            clip: self.clip,
+            z: self.z,
+        });
+    }
+
+    pub(crate) fn finish(mut self) -> DisplayList {
+        // Stable, so items with the same z keep their order
+        self.items.sort_by_key(|item| item.z);
+        DisplayList { items: self.items }
+    }
+}
+
+impl<'a> TextMeasure for DisplayListBuilder<'a> {
+    fn text_width(&mut self, text: &str, size: f32) -> f32 {
+        self.measure.text_width(text, size)
+    }
+
+    fn text_metrics(&mut self, size: f32) -> TextMetrics {
+        self.measure.text_metrics(size)
+    }
+}
+
+impl<'a> Renderer for DisplayListBuilder<'a> {
+    fn fill_rect(&mut self, pos: Vec2, size: Vec2, fill: Fill) {
+        self.push(Primitive::Rect { pos, size, fill });
+    }
+
+    fn fill_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, fill: Fill) {
+        self.push(Primitive::RoundedRect {
+            pos,
+            size,
+            radius,
+            fill,
+        });
+    }
+
+    fn stroke_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, color: Color) {
+        self.push(Primitive::StrokeRoundedRect {
+            pos,
+            size,
+            radius,
+            color,
+        });
+    }
+
+    fn text(&mut self, pos: Vec2, text: &str, size: f32, color: Color) {
+        self.push(Primitive::Text {
+            pos,
+            text: text.to_owned(),
+            size,
+            color,
+        });
+    }
+
+    fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+}
@@ -0,0 +1,170 @@
+use crate::ast;
+use crate::binding::{is_truthy, resolve_bindings, DataModel, ScopedModel};
+use crate::registry::WidgetRegistry;
+use crate::ui::{Ui, WidgetId, WidgetProps};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// State shared by the whole emission pass of a .grui file.
+pub(crate) struct EmitContext<'a> {
+    pub(crate) registry: &'a WidgetRegistry,
+    /// Application state that `{bindings}` resolve against
+    pub(crate) model: &'a dyn DataModel,
+}
+
+/// Emits a widget for `item`, with the given id or the parent's next sequential one.
+fn emit_gui_item(ui: &mut Ui, emit: &mut EmitContext, item: &ast::Item, id: Option<WidgetId>) {
+    let item_id = id.unwrap_or(ui.node.next_child_id);
+
+    // Unknown widgets are reported by validation when the file is loaded
+    let (factory, signature) = match (
+        emit.registry.get(&item.ident),
+        emit.registry.signature(&item.ident),
+    ) {
+        (Some(factory), Some(signature)) => (factory, signature),
+        _ => return,
+    };
+
+    let item = resolve_bindings(item, signature, emit.model);
+
+    if let Some(mut ctx) = factory(&item) {
+        ctx.string_uid = item.uid.clone();
+        ctx.props = WidgetProps::from_ast(&item.properties);
+
+        if let Some(ref items) = item.children {
+            emit_gui_items(
+                &mut Ui::new(&mut ctx, ui.context.nested(item_id)),
+                emit,
+                items,
+            );
+        }
+
+        match id {
+            Some(id) => ui.append_with_id(id, ctx),
+            None => ui.append(ctx),
+        };
+    }
+}
+
+pub(crate) fn emit_gui_items(ui: &mut Ui, emit: &mut EmitContext, ast: &[ast::Node]) {
+    emit_gui_nodes(ui, emit, ast, None);
+}
+
+/// Ids of widgets inside `if` and `for` blocks are hashed from the block they are in and
+/// their position in it, rather than taken from the parent's counter, so that blocks
+/// appearing, disappearing or changing length do not shift the ids of other widgets.
+/// The top bit keeps them apart from sequential ids.
+///
+/// Blocks expanded from templates are told apart by the instantiations they come from.
+fn block_scope(
+    parent: Option<u64>,
+    span: ast::Span,
+    instances: &[ast::Span],
+    discriminator: impl Hash,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (parent, span.file.0, span.start, discriminator).hash(&mut hasher);
+    for instance in instances {
+        (instance.file.0, instance.start).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn block_child_id(scope: u64, index: usize) -> WidgetId {
+    let mut hasher = DefaultHasher::new();
+    (scope, index).hash(&mut hasher);
+    WidgetId(hasher.finish() as usize | !(usize::MAX >> 1))
+}
+
+fn emit_gui_nodes(ui: &mut Ui, emit: &mut EmitContext, nodes: &[ast::Node], scope: Option<u64>) {
+    for (index, node) in nodes.iter().enumerate() {
+        match node {
+            ast::Node::Item(item) => {
+                let id = scope.map(|scope| block_child_id(scope, index));
+                emit_gui_item(ui, emit, item, id);
+            }
+            ast::Node::If(cond) => {
+                let (branch, body) = if is_truthy(emit.model, &cond.condition) {
+                    (0, cond.body.as_slice())
+                } else {
+                    (1, cond.else_body.as_deref().unwrap_or_default())
+                };
+
+                let scope = block_scope(scope, cond.span, &cond.instances, branch);
+                emit_gui_nodes(ui, emit, body, Some(scope));
+            }
+            ast::Node::For(each) => {
+                for key in emit.model.list_keys(&each.list) {
+                    let model = ScopedModel {
+                        parent: emit.model,
+                        var: &each.var,
+                        element: format!("{}.{}", each.list, key),
+                    };
+                    let mut emit = EmitContext {
+                        registry: emit.registry,
+                        model: &model,
+                    };
+
+                    let scope = block_scope(scope, each.span, &each.instances, &key);
+                    emit_gui_nodes(ui, &mut emit, &each.body, Some(scope));
+                }
+            }
+            // Includes and templates have already been expanded by the loader
+            ast::Node::Include(_) | ast::Node::Template(_) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::UiLoader;
+    use crate::ui::{UiContext, UiInteractionState, UiNode, Widget, WidgetUid};
+
+    use std::collections::HashMap;
+
+    fn emit_source(text: &str, model: &dyn DataModel) -> UiNode {
+        let registry = WidgetRegistry::default();
+        let mut loader = UiLoader::from_string("test.grui", text);
+        loader.poll(&registry);
+        assert!(loader.diagnostics().is_empty());
+
+        let mut root = UiNode::new(Widget::Vertical);
+        let state = UiInteractionState::default();
+        let mut ui = Ui::new(
+            &mut root,
+            UiContext {
+                uid: WidgetUid(Vec::new()),
+                interaction_state: &state,
+            },
+        );
+        let mut emit = EmitContext {
+            registry: &registry,
+            model,
+        };
+        emit_gui_items(&mut ui, &mut emit, loader.ast().unwrap());
+        root
+    }
+
+    #[test]
+    fn template_instances_get_their_own_block_ids() {
+        let mut model = HashMap::new();
+        model.insert("x".to_owned(), ast::Value::Bool(true));
+        let text = r#"
+            template t() { if {x} { label "a"; } }
+            template both() { t; t; }
+            t;
+            t;
+            both;
+            both;
+        "#;
+
+        let root = emit_source(text, &model);
+        let mut ids: Vec<WidgetId> = root.children.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids.len(), 6);
+        ids.sort_by_key(|id| id.0);
+        ids.dedup();
+        assert_eq!(ids.len(), 6);
+    }
+}
@@ -0,0 +1,530 @@
+use crate::ast::{self, FileId, Node};
+use crate::diagnostics::{Diagnostic, Source};
+use crate::lexer::{Comment, Lexer, Tok};
+use crate::loader::read_ui_source;
+use crate::syntax;
+
+use std::fmt::Write;
+use std::path::Path;
+
+const INDENT: &str = "    ";
+
+/// Formats parsed .grui nodes in the canonical style. Without the source text there are
+/// no comments or blank lines to preserve; see `format_source` for those.
+pub fn format_nodes(nodes: &[Node]) -> String {
+    let mut f = Formatter::new(None, &[]);
+    f.nodes(nodes);
+    f.out
+}
+
+/// Re-formats .grui source text in the canonical style, keeping comments, and single blank
+/// lines between items.
+pub fn format_source(source: &Source) -> Result<String, Diagnostic> {
+    let mut lexer = Lexer::new(&source.text);
+    let nodes = syntax::parse(FileId::default(), &mut lexer)
+        .map_err(|e| Diagnostic::from_parse_error(source, &e))?;
+
+    let mut f = Formatter::new(Some(&source.text), lexer.comments());
+    f.nodes(&nodes);
+    f.leading(source.text.len());
+    Ok(f.out.trim_end().to_owned() + "\n")
+}
+
+struct Formatter<'a> {
+    source: Option<&'a str>,
+    comments: &'a [Comment],
+    out: String,
+    depth: usize,
+    /// Source offset just past the last thing written, for placing comments and blank lines
+    last_end: Option<usize>,
+    /// Whether nothing has been written since the current block was opened
+    block_start: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: Option<&'a str>, comments: &'a [Comment]) -> Self {
+        Self {
+            source,
+            comments,
+            out: String::new(),
+            depth: 0,
+            last_end: None,
+            block_start: true,
+        }
+    }
+
+    /// Source text between the last thing written and `offset`, if known
+    fn gap(&self, offset: usize) -> Option<&'a str> {
+        self.source?.get(self.last_end?..offset)
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+        self.block_start = false;
+    }
+
+    /// Writes the comments preceding `offset`, and a blank line if the source had one there.
+    fn leading(&mut self, offset: usize) {
+        while let Some((&comment, rest)) = self.comments.split_first() {
+            if comment.start >= offset {
+                break;
+            }
+            self.comments = rest;
+
+            let text = &self.source.unwrap()[comment.start..comment.end];
+
+            match self.gap(comment.start) {
+                // On the same line as the previous item, or the header of the current block
+                Some(gap) if !gap.contains('\n') && self.out.ends_with('\n') => {
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out.push_str(text);
+                    self.out.push('\n');
+                    self.block_start = false;
+                }
+                _ => {
+                    self.blank_line(comment.start);
+                    self.line(text);
+                }
+            }
+
+            self.last_end = Some(comment.end);
+        }
+
+        self.blank_line(offset);
+    }
+
+    fn blank_line(&mut self, offset: usize) {
+        if self.block_start {
+            return;
+        }
+
+        if self
+            .gap(offset)
+            .is_some_and(|gap| gap.matches('\n').count() > 1)
+        {
+            self.out.push('\n');
+        }
+    }
+
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Item(item) => {
+                self.leading(item.span.start);
+
+                let mut literals = self.string_literals(item.span.start).into_iter();
+                let mut header = String::new();
+                if let Some(ref uid) = item.uid {
+                    write!(header, "#{} ", uid).unwrap();
+                }
+                header.push_str(&item.ident);
+                for value in &item.values {
+                    header.push(' ');
+                    write_literal(&mut header, value, &mut literals);
+                }
+                if !item.properties.is_empty() {
+                    header.push_str(" (");
+                    for (i, prop) in item.properties.0.iter().enumerate() {
+                        if i > 0 {
+                            header.push_str(", ");
+                        }
+                        write!(header, "{}: ", prop.name).unwrap();
+                        write_literal(&mut header, &prop.value, &mut literals);
+                    }
+                    header.push(')');
+                }
+
+                match item.children {
+                    None => {
+                        header.push(';');
+                        self.line(&header);
+                        self.last_end = Some(item.span.end);
+                    }
+                    Some(ref children) => self.block(&header, item.span, children),
+                }
+            }
+            Node::Include(include) => {
+                self.leading(include.span.start);
+                let mut literals = self.string_literals(include.span.start).into_iter();
+                let mut line = String::from("include ");
+                let path = ast::Value::String(include.path.clone());
+                write_literal(&mut line, &path, &mut literals);
+                line.push(';');
+                self.line(&line);
+                self.last_end = Some(include.span.end);
+            }
+            Node::Template(template) => {
+                self.leading(template.span.start);
+                let header = format!("template {}({})", template.name, template.params.join(", "));
+                self.block(&header, template.span, &template.body);
+            }
+            Node::If(cond) => {
+                self.leading(cond.span.start);
+                self.if_chain(cond, false);
+            }
+            Node::For(each) => {
+                self.leading(each.span.start);
+                let header = format!("for {} in {{{}}}", each.var, each.list);
+                self.block(&header, each.span, &each.body);
+            }
+        }
+    }
+
+    /// `header { children }`, where `span` ends with the closing brace
+    fn block(&mut self, header: &str, span: ast::Span, children: &[Node]) {
+        self.open(header, span, false);
+        self.nodes(children);
+        self.close(span.end.saturating_sub(1));
+        self.last_end = Some(span.end);
+    }
+
+    /// Writes `header {`, either on a new line or continuing the last one
+    fn open(&mut self, header: &str, span: ast::Span, inline: bool) {
+        if inline {
+            writeln!(self.out, "{} {{", header).unwrap();
+        } else {
+            self.line(&format!("{} {{", header));
+        }
+
+        // Lets comments on the header line stay there
+        self.last_end = Some(span.start);
+        self.block_start = true;
+        self.depth += 1;
+    }
+
+    /// Writes the comments before `end`, then the closing brace. Empty blocks become `{}`.
+    fn close(&mut self, end: usize) {
+        self.leading(end);
+        self.depth -= 1;
+
+        if self.block_start {
+            self.out.pop();
+            self.out.push_str("}\n");
+            self.block_start = false;
+        } else {
+            self.line("}");
+        }
+    }
+
+    /// Source text of the string literals in the header of the item or include starting at
+    /// `start`, in order, so that raw strings can be written back as they were
+    fn string_literals(&self, start: usize) -> Vec<&'a str> {
+        let text = match self.source.and_then(|source| source.get(start..)) {
+            Some(text) => text,
+            None => return Vec::new(),
+        };
+
+        Lexer::new(text)
+            .map_while(|tok| match tok {
+                Ok((_, Tok::LBrace, _)) | Ok((_, Tok::Semi, _)) | Err(_) => None,
+                Ok((start, tok, end)) => Some(match tok {
+                    Tok::String(_) => Some(&text[start..end]),
+                    _ => None,
+                }),
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Source offsets of the `}` closing the body of `cond`, and of the `else` keyword after
+    /// it, which are not kept in the AST
+    fn else_offsets(&self, cond: &ast::If) -> Option<(usize, usize)> {
+        let from = cond
+            .body
+            .last()
+            .map_or(cond.span.start, |n| node_span(n).end);
+        let text = self.source?.get(from..cond.span.end)?;
+
+        let mut close = None;
+        for tok in Lexer::new(text) {
+            match tok {
+                Ok((i, Tok::RBrace, _)) => close = Some(from + i),
+                Ok((i, Tok::Else, _)) => return close.map(|close| (close, from + i)),
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    fn if_chain(&mut self, cond: &ast::If, inline: bool) {
+        self.open(&format!("if {{{}}}", cond.condition), cond.span, inline);
+        self.nodes(&cond.body);
+
+        let else_body = match cond.else_body {
+            Some(ref body) => body,
+            None => {
+                self.close(cond.span.end.saturating_sub(1));
+                self.last_end = Some(cond.span.end);
+                return;
+            }
+        };
+
+        let (close, else_start) = self
+            .else_offsets(cond)
+            .unwrap_or((cond.span.end, cond.span.end));
+        self.close(close);
+
+        // Comments between the brace and `else` stay after the brace
+        self.last_end = Some(close + 1);
+        if self.comments.first().is_some_and(|c| c.start < else_start) {
+            self.leading(else_start);
+            for _ in 0..self.depth {
+                self.out.push_str(INDENT);
+            }
+            self.out.push_str("else ");
+        } else {
+            self.out.pop();
+            self.out.push_str(" else ");
+        }
+
+        match else_body.as_slice() {
+            [Node::If(nested)] => self.if_chain(nested, true),
+            body => {
+                self.out.push_str("{\n");
+                self.block_start = true;
+                self.depth += 1;
+                self.nodes(body);
+                self.close(cond.span.end.saturating_sub(1));
+                self.last_end = Some(cond.span.end);
+            }
+        }
+    }
+}
+
+fn node_span(node: &Node) -> ast::Span {
+    match node {
+        Node::Item(item) => item.span,
+        Node::Include(include) => include.span,
+        Node::Template(template) => template.span,
+        Node::If(cond) => cond.span,
+        Node::For(each) => each.span,
+    }
+}
+
+/// Writes `value`, or the next of `literals` for strings which were written as raw strings
+fn write_literal<'a>(
+    out: &mut String,
+    value: &ast::Value,
+    literals: &mut impl Iterator<Item = &'a str>,
+) {
+    if let ast::Value::String(_) = value {
+        if let Some(literal) = literals.next().filter(|literal| literal.starts_with('r')) {
+            out.push_str(literal);
+            return;
+        }
+    }
+
+    write_value(out, value);
+}
+
+/// Numbers which can be read back. Infinity and NaN have no literal, and only come from code,
+/// so they are written as the closest finite value.
+fn write_number(out: &mut String, v: f32) {
+    let v = if v.is_nan() {
+        0.0
+    } else {
+        v.clamp(f32::MIN, f32::MAX)
+    };
+    write!(out, "{}", v).unwrap();
+}
+
+fn write_value(out: &mut String, value: &ast::Value) {
+    match value {
+        ast::Value::Int(v) => write!(out, "{}", v).unwrap(),
+        ast::Value::Float(v) => {
+            // Keep the decimal point, so the value reads back as a float
+            let start = out.len();
+            write_number(out, *v);
+            if !out[start..].contains('.') {
+                out.push_str(".0");
+            }
+        }
+        ast::Value::Bool(v) => write!(out, "{}", v).unwrap(),
+        ast::Value::Color(c) => {
+            write!(out, "#{:02x}{:02x}{:02x}", c.r, c.g, c.b).unwrap();
+            if c.a != 255 {
+                write!(out, "{:02x}", c.a).unwrap();
+            }
+        }
+        ast::Value::Dimension(v, unit) => {
+            write_number(out, *v);
+            write!(out, "{}", unit).unwrap();
+        }
+        ast::Value::Ident(s) => out.push_str(s),
+        ast::Value::String(s) => write_string(out, s),
+        ast::Value::Binding(path) => write!(out, "{{{}}}", path).unwrap(),
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => write!(out, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// `grui fmt [--check] FILE...`: rewrites the files in the canonical style, or with `--check`,
+/// only lists the ones which are not formatted. Returns the process exit code.
+pub fn run_fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|a| a == "--check");
+    let paths: Vec<&String> = args.iter().filter(|a| *a != "--check").collect();
+
+    if paths.is_empty() {
+        eprintln!("usage: grui fmt [--check] FILE...");
+        return 2;
+    }
+
+    let mut code = 0;
+
+    for path in paths {
+        match fmt_file(Path::new(path), check) {
+            Ok(false) => (),
+            Ok(true) if check => {
+                println!("{} is not formatted", path);
+                code = 1;
+            }
+            Ok(true) => println!("formatted {}", path),
+            Err(diagnostic) => {
+                eprintln!("{}", diagnostic);
+                code = 1;
+            }
+        }
+    }
+
+    code
+}
+
+/// Returns whether the file needed formatting. Unformatted files are rewritten,
+/// unless `check` is set.
+fn fmt_file(path: &Path, check: bool) -> Result<bool, Diagnostic> {
+    let source = read_ui_source(path)?;
+    let formatted = format_source(&source)?;
+
+    if formatted == source.text {
+        return Ok(false);
+    }
+
+    if !check {
+        std::fs::write(path, formatted)
+            .map_err(|e| Diagnostic::whole_file(source.name.as_str(), e.to_string()))?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r##"// header
+include "common.grui";
+
+if {a} {
+    label "a";
+} // after if
+else if {b} {
+label r"multi
+line" (color: #ff000080);
+}   else {
+    button "b\t\"c\"" (width: 1.5, height: 20px);
+} // after else
+
+template card(title) {
+    vertical { // header line
+        label r#"say "hi""#; // trailing
+
+
+        for item in {items} { label {item.name}; }
+    }
+}
+"##;
+
+    fn fmt(text: &str) -> String {
+        format_source(&Source::new("test.grui", text)).unwrap()
+    }
+
+    /// The canonical form of the parsed nodes, which leaves out spans and comments
+    fn parsed(text: &str) -> String {
+        let nodes = syntax::parse(FileId::default(), Lexer::new(text)).unwrap();
+        format_nodes(&nodes)
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let once = fmt(SAMPLE);
+        assert_eq!(fmt(&once), once);
+    }
+
+    #[test]
+    fn formatted_source_parses_to_the_same_nodes() {
+        assert_eq!(parsed(&fmt(SAMPLE)), parsed(SAMPLE));
+    }
+
+    #[test]
+    fn comments_are_kept_in_place() {
+        let formatted = fmt(SAMPLE);
+
+        assert!(formatted.starts_with("// header\n"));
+        assert!(formatted.contains("} // after if\nelse if {b} {\n"));
+        assert!(formatted.contains("} // after else\n"));
+        assert!(formatted.contains("    vertical { // header line\n"));
+        assert!(formatted.contains(r##"label r#"say "hi""#; // trailing"##));
+    }
+
+    #[test]
+    fn raw_strings_are_kept() {
+        let formatted = fmt(SAMPLE);
+
+        assert!(formatted.contains("label r\"multi\nline\" (color: #ff000080);"));
+        assert!(formatted.contains(r#"button "b\t\"c\"" (width: 1.5, height: 20px);"#));
+    }
+
+    #[test]
+    fn numbers_out_of_range_are_rejected() {
+        let huge = format!("label \"x\" (width: 1{}.0);", "0".repeat(40));
+        assert!(format_source(&Source::new("test.grui", huge)).is_err());
+    }
+
+    #[test]
+    fn non_finite_numbers_are_written_as_finite_literals() {
+        let nodes = [Node::Item(ast::Item {
+            values: vec![ast::Value::Float(f32::INFINITY)],
+            ..parsed_item("label 0.0;")
+        })];
+        let formatted = format_nodes(&nodes);
+
+        assert!(!formatted.contains("inf"));
+        parsed(&formatted);
+    }
+
+    fn parsed_item(text: &str) -> ast::Item {
+        match syntax::parse(FileId::default(), Lexer::new(text))
+            .unwrap()
+            .remove(0)
+        {
+            Node::Item(item) => item,
+            _ => unreachable!(),
+        }
+    }
+}
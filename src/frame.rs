@@ -0,0 +1,579 @@
+use crate::ast;
+use crate::binding::DataModel;
+use crate::diagnostics::Diagnostic;
+use crate::display_list::{DisplayList, DisplayListBuilder};
+use crate::emit::{emit_gui_items, EmitContext};
+use crate::layout::{
+    flatten_layout, flatten_widgets, hit_test, padding, FlattenedLayout, FlattenedWidgetNode,
+    LayoutCache, LayoutStats, BUTTON_FONT_SIZE, LABEL_FONT_SIZE,
+};
+use crate::registry::WidgetRegistry;
+use crate::render::{with_clip, Fill, Rect, Renderer};
+use crate::scroll::{apply_scroll, handle_scroll_input, scrollbars};
+use crate::ui::{
+    EventPropagation, GenericWidget, Ui, UiContext, UiInteractionState, UiNode, Widget,
+    WidgetEvent, WidgetProps, WidgetResponse, WidgetUid,
+};
+
+use glam::{vec2, Vec2};
+
+/// Input for a frame of the UI, as reported by the host window or simulated
+#[derive(Debug, Clone, Copy)]
+struct FrameInput {
+    mouse: Vec2,
+    mouse_down: bool,
+    /// Wheel movement since the last frame
+    wheel: Vec2,
+    /// Size of the area the UI is drawn into
+    size: Vec2,
+}
+
+/// What a frame of the UI is built from
+pub struct FrameSource<'a> {
+    /// The loaded .grui file, if it has loaded successfully
+    pub ast: Option<&'a [ast::Node]>,
+    /// Problems to show on top of the UI
+    pub diagnostics: &'a [Diagnostic],
+    pub registry: &'a WidgetRegistry,
+    pub model: &'a dyn DataModel,
+}
+
+/// Input from the window the UI is shown in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    MouseMove(Vec2),
+    MouseDown,
+    MouseUp,
+    /// Wheel or touchpad scrolling, in pixels. Positive values scroll towards the top and the
+    /// left of the content, like turning the wheel away from the user.
+    Wheel(Vec2),
+    /// The area the UI is drawn into changed size
+    Resize(Vec2),
+}
+
+/// Z-order of the layers the frame pass draws. Widgets are drawn around `Z_WIDGETS`, moved
+/// up or down by their `z_index`, and always below the tooltip.
+const Z_WIDGETS: i32 = 0;
+const Z_TOOLTIP: i32 = i32::MAX - 1;
+const Z_OVERLAY: i32 = i32::MAX;
+
+fn widget_z(layout: &FlattenedLayout) -> i32 {
+    Z_WIDGETS.saturating_add(layout.z).min(Z_TOOLTIP - 1)
+}
+
+/// Runs the UI inside an application which owns the window, the event loop and the drawing.
+/// Input events are fed in as they arrive, and each call to `frame` emits the UI and draws
+/// it with the given renderer.
+pub struct UiHost {
+    interaction_state: UiInteractionState,
+    input: FrameInput,
+    /// Layout of the previous frame, so that only the widgets which changed are laid out again
+    layout_cache: LayoutCache,
+    /// Widgets kept from one frame to the next, and updated to match what each frame emits
+    tree: UiNode,
+}
+
+impl UiHost {
+    /// The mouse starts outside the UI, until the first `InputEvent::MouseMove`.
+    pub fn new(size: Vec2) -> Self {
+        Self {
+            interaction_state: UiInteractionState::default(),
+            input: FrameInput {
+                mouse: vec2(-1.0, -1.0),
+                mouse_down: false,
+                wheel: Vec2::zero(),
+                size,
+            },
+            layout_cache: LayoutCache::new(),
+            tree: UiNode::new(Widget::Vertical),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::MouseMove(pos) => self.input.mouse = pos,
+            InputEvent::MouseDown => self.input.mouse_down = true,
+            InputEvent::MouseUp => self.input.mouse_down = false,
+            InputEvent::Wheel(delta) => self.input.wheel += delta,
+            InputEvent::Resize(size) => self.input.size = size,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.input.size
+    }
+
+    /// What the user is interacting with, as of the last frame
+    pub fn interaction_state(&self) -> &UiInteractionState {
+        &self.interaction_state
+    }
+
+    /// The widgets as of the last frame. Nodes stay the same from frame to frame as long as
+    /// they are emitted in the same place, or with the same `#name`, and as the same kind of
+    /// widget.
+    pub fn tree(&self) -> &UiNode {
+        &self.tree
+    }
+
+    /// How much of the layout the last frame computed again
+    pub fn layout_stats(&self) -> LayoutStats {
+        self.layout_cache.stats()
+    }
+
+    /// Emits, lays out and handles input for one frame of the UI, and returns what it draws.
+    /// Text is measured with `measure`, which should use the same fonts as the renderer the
+    /// list is drawn with. `code` runs right after emission, to let application code add to
+    /// and query the tree.
+    pub fn display_list(
+        &mut self,
+        measure: &mut dyn Renderer,
+        source: &FrameSource,
+        code: &mut dyn FnMut(&mut Ui),
+    ) -> DisplayList {
+        let list = build_display_list(
+            measure,
+            &mut self.interaction_state,
+            &mut self.layout_cache,
+            &mut self.tree,
+            &self.input,
+            source,
+            code,
+        );
+        self.input.wheel = Vec2::zero();
+        list
+    }
+
+    /// Runs a frame like `display_list`, and draws the result with `renderer`.
+    pub fn frame(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        source: &FrameSource,
+        code: &mut dyn FnMut(&mut Ui),
+    ) -> DisplayList {
+        let list = self.display_list(renderer, source, code);
+        list.draw(renderer);
+        list
+    }
+}
+
+fn build_display_list(
+    measure: &mut dyn Renderer,
+    interaction_state: &mut UiInteractionState,
+    layout_cache: &mut LayoutCache,
+    tree: &mut UiNode,
+    input: &FrameInput,
+    source: &FrameSource,
+    code: &mut dyn FnMut(&mut Ui),
+) -> DisplayList {
+    let mut list = DisplayListBuilder::new(measure);
+    let mouse = input.mouse;
+    let prev_mouse_down = interaction_state.mouse_down;
+
+    interaction_state.mouse_down = input.mouse_down;
+    interaction_state.hover_widget = None;
+    interaction_state.mouse_released = !interaction_state.mouse_down && prev_mouse_down;
+    interaction_state.mouse_pressed = interaction_state.mouse_down && !prev_mouse_down;
+
+    if let Some(gui_ast) = source.ast {
+        let mut ui_top_level = UiNode::new(Widget::Vertical);
+        let mut ui_ctx = Ui::new(
+            &mut ui_top_level,
+            UiContext {
+                uid: WidgetUid(Vec::new()),
+                interaction_state,
+            },
+        );
+
+        let mut emit = EmitContext {
+            registry: source.registry,
+            model: source.model,
+        };
+        emit_gui_items(&mut ui_ctx, &mut emit, gui_ast);
+
+        //dbg!(&ui_ctx);
+        code(&mut ui_ctx);
+
+        // Behaviors keep their state in the retained nodes
+        let changes = tree.reconcile(ui_top_level);
+        interaction_state.follow_widgets(&changes);
+
+        let ui_layout = layout_cache.layout(tree, &mut list, input.size);
+
+        //dbg!(&ui_layout);
+
+        let mut flat_widgets = flatten_widgets(tree);
+        let mut flat_layout = flatten_layout(&ui_layout);
+        apply_scroll(
+            &flat_widgets,
+            &mut flat_layout,
+            &mut interaction_state.scroll_offsets,
+        );
+
+        interaction_state.widget_responses.clear();
+
+        let tooltip;
+        let scrolling;
+        // Widgets under the mouse, topmost first, which is the order they get events in
+        let mouse_hover_widgets;
+
+        {
+            scrolling = handle_scroll_input(
+                interaction_state,
+                &flat_widgets,
+                &flat_layout,
+                &hit_test(&flat_layout, mouse),
+                mouse,
+                input.wheel,
+            );
+
+            // Scrolling moves the content under the mouse
+            flat_layout = flatten_layout(&ui_layout);
+            apply_scroll(
+                &flat_widgets,
+                &mut flat_layout,
+                &mut interaction_state.scroll_offsets,
+            );
+            mouse_hover_widgets = hit_test(&flat_layout, mouse);
+
+            // The topmost widget under the mouse which has a tooltip wins
+            tooltip = mouse_hover_widgets
+                .iter()
+                .find(|wi| flat_widgets[**wi].props.tooltip.is_some())
+                .map(|wi| {
+                    let widget = &flat_widgets[*wi];
+                    (widget.uid.clone(), widget.props.tooltip.clone().unwrap())
+                });
+
+            let mut widget_events = vec![];
+
+            if scrolling {
+                // The mouse is used by a scrollbar
+            } else if interaction_state.mouse_pressed {
+                widget_events.push(WidgetEvent::BeginActive);
+            }
+
+            if interaction_state.mouse_released {
+                widget_events.push(WidgetEvent::EndActive);
+            }
+
+            for event in widget_events {
+                for wi in mouse_hover_widgets.iter() {
+                    if !flat_widgets[*wi].props.enabled {
+                        continue;
+                    }
+
+                    let mut should_stop = false;
+
+                    let mut responses: Vec<WidgetResponse> = Vec::new();
+
+                    if let Widget::Generic(ref mut w) = flat_widgets[*wi].widget {
+                        for b in &mut w.behaviors {
+                            should_stop |=
+                                EventPropagation::Stop == b.handle_event(&event, &mut responses);
+                        }
+                    }
+
+                    //dbg!(&responses);
+
+                    interaction_state
+                        .widget_responses
+                        .insert(flat_widgets[*wi].uid.clone(), responses);
+
+                    if should_stop {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !interaction_state.mouse_down && !prev_mouse_down {
+            interaction_state.drag_begin_widget = None;
+        }
+
+        // Only the topmost button under the mouse is hovered
+        let hovered_button = mouse_hover_widgets
+            .iter()
+            .map(|wi| &flat_widgets[*wi])
+            .find(|widget| matches!(widget.widget, Widget::Button(_)));
+
+        if let Some(FlattenedWidgetNode { uid, props, .. }) = hovered_button {
+            if props.enabled && !scrolling {
+                interaction_state.hover_widget = Some(uid.to_owned());
+
+                if interaction_state.mouse_pressed {
+                    interaction_state.drag_begin_widget = Some(uid.to_owned());
+                }
+            }
+        }
+
+        for (
+            FlattenedWidgetNode {
+                uid, widget, props, ..
+            },
+            layout,
+        ) in flat_widgets.iter().zip(&flat_layout)
+        {
+            list.widget = Some(uid.clone());
+            list.z = widget_z(layout);
+            list.set_clip(layout.clip);
+
+            match widget {
+                Widget::Label(s) => {
+                    let pos = layout.offset + padding(widget, props);
+                    let color = props.color.unwrap_or(LABEL_COLOR);
+                    draw_label(&mut list, s, pos.x(), pos.y(), color)
+                }
+                Widget::Button(s) => {
+                    let color =
+                        button_color(props, interaction_state.hover_widget.as_ref() == Some(uid));
+
+                    draw_button(
+                        &mut list,
+                        s,
+                        layout.offset.x(),
+                        layout.offset.y(),
+                        layout.extent.x(),
+                        layout.extent.y(),
+                        color,
+                        interaction_state.drag_begin_widget.as_ref() == Some(uid),
+                    )
+                }
+                Widget::Generic(GenericWidget { draw_style, .. }) if *draw_style == "button" => {
+                    let color =
+                        button_color(props, interaction_state.hover_widget.as_ref() == Some(uid));
+
+                    draw_button(
+                        &mut list,
+                        "",
+                        layout.offset.x(),
+                        layout.offset.y(),
+                        layout.extent.x(),
+                        layout.extent.y(),
+                        color,
+                        false,
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        // Scrollbars go over the content of their container, which was drawn after it
+        for (FlattenedWidgetNode { uid, .. }, layout) in flat_widgets.iter().zip(&flat_layout) {
+            let dragged = interaction_state.scroll_drag.as_ref();
+            let offset = interaction_state.scroll_offset(uid);
+
+            list.widget = Some(uid.clone());
+            list.z = widget_z(layout);
+            list.set_clip(layout.clip);
+
+            for bar in scrollbars(layout, offset) {
+                let active = dragged.is_some_and(|drag| drag.is_dragging(uid, bar.axis))
+                    || bar.thumb.contains(mouse);
+                draw_scrollbar(&mut list, bar.track, bar.thumb, active);
+            }
+        }
+        list.set_clip(None);
+
+        if let Some((uid, text)) = tooltip {
+            list.widget = Some(uid);
+            list.z = Z_TOOLTIP;
+            draw_tooltip(&mut list, &text, mouse.x(), mouse.y());
+        }
+    }
+
+    list.widget = None;
+    list.z = Z_OVERLAY;
+    draw_error_overlay(
+        &mut list,
+        source.diagnostics,
+        input.size.x(),
+        input.size.y(),
+    );
+
+    list.finish()
+}
+
+const LABEL_COLOR: ast::Color = ast::Color::rgba(255, 255, 255, 128);
+const BUTTON_COLOR: ast::Color = ast::Color::rgba(0, 96, 128, 255);
+
+/// Background of a button, lighter when hovered, and grey when disabled
+fn button_color(props: &WidgetProps, hovered: bool) -> ast::Color {
+    let color = props.color.unwrap_or(BUTTON_COLOR);
+
+    if !props.enabled {
+        ast::Color::rgba(64, 64, 68, 255)
+    } else if hovered {
+        let lighter = |c: u8| c.saturating_add(16);
+        ast::Color::rgba(
+            lighter(color.r),
+            lighter(color.g),
+            lighter(color.b),
+            color.a,
+        )
+    } else {
+        color
+    }
+}
+
+fn draw_label(r: &mut dyn Renderer, text: &str, x: f32, y: f32, color: ast::Color) {
+    let line_height = r.text_metrics(LABEL_FONT_SIZE).line_height;
+
+    for (i, line) in text.split('\n').enumerate() {
+        r.text(
+            vec2(x, y + (i as f32 + 0.5) * line_height),
+            line,
+            LABEL_FONT_SIZE,
+            color,
+        );
+    }
+}
+
+fn draw_tooltip(r: &mut dyn Renderer, text: &str, x: f32, y: f32) {
+    let size = 16.0;
+    let tw = r.text_width(text, size);
+    let th = r.text_metrics(size).line_height;
+    let (x, y, w, h) = (x + 12.0, y + 16.0, tw + 12.0, (th + 3.0).round());
+
+    r.fill_rounded_rect(
+        vec2(x, y),
+        vec2(w, h),
+        3.0,
+        Fill::Solid(ast::Color::rgba(16, 16, 20, 230)),
+    );
+
+    r.text(
+        vec2(x + 6.0, y + h * 0.5),
+        text,
+        size,
+        ast::Color::rgba(255, 255, 255, 224),
+    );
+}
+
+fn draw_error_overlay(r: &mut dyn Renderer, diagnostics: &[Diagnostic], width: f32, height: f32) {
+    if diagnostics.is_empty() {
+        return;
+    }
+
+    let line_height = 20.0;
+    let padding = 8.0;
+
+    let mut lines = Vec::new();
+    for d in diagnostics {
+        lines.push(d.headline());
+        if let Some(ref loc) = d.location {
+            lines.push(format!("    {}", loc.source_line.trim()));
+        }
+        if let Some(expected) = d.expected_list() {
+            lines.push(format!("    {}", expected));
+        }
+    }
+
+    let box_height = lines.len() as f32 * line_height + padding * 2.0;
+    let y0 = height - box_height;
+
+    r.fill_rect(
+        vec2(0.0, y0),
+        vec2(width, box_height),
+        Fill::Solid(ast::Color::rgba(48, 0, 0, 224)),
+    );
+
+    for (i, line) in lines.iter().enumerate() {
+        r.text(
+            vec2(padding, y0 + padding + (i as f32 + 0.5) * line_height),
+            line,
+            16.0,
+            ast::Color::rgba(255, 160, 160, 255),
+        );
+    }
+}
+
+fn draw_scrollbar(r: &mut dyn Renderer, track: Rect, thumb: Rect, active: bool) {
+    let radius = track.size.min_element() * 0.5;
+
+    r.fill_rounded_rect(
+        track.pos,
+        track.size,
+        radius,
+        Fill::Solid(ast::Color::rgba(0, 0, 0, 48)),
+    );
+    r.fill_rounded_rect(
+        thumb.pos,
+        thumb.size,
+        radius,
+        Fill::Solid(ast::Color::rgba(
+            255,
+            255,
+            255,
+            if active { 160 } else { 96 },
+        )),
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_button(
+    r: &mut dyn Renderer,
+    text: &str,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    color: ast::Color,
+    pressed: bool,
+) {
+    let corner_radius = 4.0;
+    let color_is_black = color == ast::Color::rgba(0, 0, 0, 0);
+
+    // button background
+    let (pos, size) = (vec2(x + 1.0, y + 1.0), vec2(w - 2.0, h - 2.0));
+    let inside = Rect::new(pos, size);
+    if !color_is_black {
+        r.fill_rounded_rect(pos, size, corner_radius - 0.5, Fill::Solid(color));
+    }
+
+    let c0 = ast::Color::rgba(255, 255, 255, if color_is_black { 16 } else { 32 });
+    let c1 = ast::Color::rgba(0, 0, 0, if color_is_black { 16 } else { 32 });
+
+    r.fill_rounded_rect(
+        pos,
+        size,
+        corner_radius - 0.5,
+        Fill::LinearGradient {
+            start: vec2(x, y),
+            end: vec2(x, y + h),
+            start_color: if !pressed { c0 } else { c1 },
+            end_color: if !pressed { c1 } else { c0 },
+        },
+    );
+
+    // button border
+    r.stroke_rounded_rect(
+        vec2(x + 0.5, y + 0.5),
+        vec2(w - 1.0, h - 1.0),
+        corner_radius - 0.5,
+        ast::Color::rgba(0, 0, 0, 48),
+    );
+
+    let size = BUTTON_FONT_SIZE;
+    let line_height = r.text_metrics(size).line_height;
+    let line_count = text.split('\n').count() as f32;
+
+    // Text wider than the button is cut off at the border
+    with_clip(r, inside, |r| {
+        // Each line is centered on its own, like `measure_text` measures them
+        for (i, line) in text.split('\n').enumerate() {
+            let tw = r.text_width(line, size);
+            let pos = vec2(
+                x + w * 0.5 - tw * 0.5,
+                y + h * 0.5 + (i as f32 + 0.5 - line_count * 0.5) * line_height,
+            );
+
+            r.text(
+                pos - vec2(0.0, 1.0),
+                line,
+                size,
+                ast::Color::rgba(0, 0, 0, 160),
+            );
+            r.text(pos, line, size, ast::Color::rgba(255, 255, 255, 160));
+        }
+    });
+}
@@ -0,0 +1,458 @@
+use crate::ast;
+use crate::layout::{align_in, axis, Align, AxisProps, Measured};
+use crate::render::Rect;
+use crate::ui::{UiNode, WidgetProps};
+
+use glam::{vec2, Vec2};
+
+use std::fmt;
+
+/// Size of a row or column of a grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Track {
+    /// Fixed size, such as `120px` or just `120`
+    Px(f32),
+    /// Fits the largest widget in the track
+    Auto,
+    /// Share of the space left over by the other tracks, such as `1fr`
+    Fr(f32),
+}
+
+impl Track {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let number = |digits: &str| {
+            digits
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite() && *v >= 0.0)
+        };
+        let track = if s == "auto" {
+            Some(Track::Auto)
+        } else if let Some(digits) = s.strip_suffix("fr") {
+            number(digits).map(Track::Fr)
+        } else {
+            number(s.strip_suffix("px").unwrap_or(s)).map(Track::Px)
+        };
+
+        track.ok_or_else(|| {
+            format!(
+                "invalid track `{}`, expected `auto`, a size such as `120px`, or a fraction such as `1fr`",
+                s
+            )
+        })
+    }
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Track::Px(v) => write!(f, "{}px", v),
+            Track::Auto => write!(f, "auto"),
+            Track::Fr(v) => write!(f, "{}fr", v),
+        }
+    }
+}
+
+/// Parses a space-separated list of tracks, such as `"auto 1fr 120px"`.
+pub fn parse_tracks(s: &str) -> Result<Vec<Track>, String> {
+    s.split_whitespace().map(Track::parse).collect()
+}
+
+pub fn format_tracks(tracks: &[Track]) -> String {
+    let tracks: Vec<String> = tracks.iter().map(Track::to_string).collect();
+    tracks.join(" ")
+}
+
+/// Highest `row` and `column`, and largest span, a widget can have. Larger ones are clamped,
+/// as every row up to the widget's is laid out.
+pub const MAX_GRID_LINE: usize = 1000;
+
+/// Properties placing a widget in the cells of a grid
+const CELL_PROPERTIES: &[&str] = &["row", "column", "row_span", "column_span"];
+
+/// Reports `columns` and `rows` properties which are not valid track lists
+pub fn check_tracks(item: &ast::Item) -> Vec<(ast::Span, String)> {
+    item.properties
+        .0
+        .iter()
+        .filter(|prop| prop.name == "columns" || prop.name == "rows")
+        .filter_map(|prop| match prop.value {
+            ast::Value::String(ref s) => parse_tracks(s)
+                .err()
+                .map(|err| (prop.span, format!("property `{}`: {}", prop.name, err))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Reports cell properties which are not whole numbers from 1 to `MAX_GRID_LINE`. Any widget
+/// can have them, as they are read by the grid it is in.
+pub fn check_cells(item: &ast::Item) -> Vec<(ast::Span, String)> {
+    item.properties
+        .0
+        .iter()
+        .filter(|prop| CELL_PROPERTIES.contains(&prop.name.as_str()))
+        .filter_map(|prop| {
+            let value = match prop.value {
+                ast::Value::Int(v) => v as f32,
+                ast::Value::Float(v) => v,
+                _ => return None,
+            };
+
+            if value.fract() == 0.0 && (1.0..=MAX_GRID_LINE as f32).contains(&value) {
+                None
+            } else {
+                Some((
+                    prop.span,
+                    format!(
+                        "property `{}` expects a whole number from 1 to {}",
+                        prop.name, MAX_GRID_LINE
+                    ),
+                ))
+            }
+        })
+        .collect()
+}
+
+/// The tracks of a grid container. Rows which are not defined, but needed to fit all the
+/// children, are `auto`, and a grid without columns has a single `auto` one.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GridSpec {
+    pub columns: Vec<Track>,
+    pub rows: Vec<Track>,
+}
+
+/// The cells a child covers, as first column and row, and their counts
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    start: [usize; 2],
+    span: [usize; 2],
+}
+
+/// Placement of the children of a grid, and the natural sizes of its columns and rows
+#[derive(Debug)]
+pub(crate) struct GridMeasure {
+    cells: Vec<Cell>,
+    tracks: [Vec<Track>; 2],
+    natural: [Vec<f32>; 2],
+}
+
+/// Which cells of a grid are taken, row by row
+struct Occupancy {
+    columns: usize,
+    rows: Vec<Vec<bool>>,
+}
+
+impl Occupancy {
+    fn is_free(&self, cell: &Cell) -> bool {
+        let [column, row] = cell.start;
+        (row..row + cell.span[1]).all(|r| {
+            self.rows
+                .get(r)
+                .is_none_or(|taken| !taken[column..column + cell.span[0]].contains(&true))
+        })
+    }
+
+    fn take(&mut self, cell: &Cell) {
+        let [column, row] = cell.start;
+        let columns = self.columns;
+        for r in row..row + cell.span[1] {
+            if self.rows.len() <= r {
+                self.rows.resize_with(r + 1, || vec![false; columns]);
+            }
+            for taken in &mut self.rows[r][column..column + cell.span[0]] {
+                *taken = true;
+            }
+        }
+    }
+}
+
+/// Puts children with a `row` and `column` where they ask to be, and the others in the first
+/// free cells which fit them. Children are placed in order, going through the grid row by row.
+fn place_children<'a>(
+    columns: usize,
+    children: impl Iterator<Item = &'a WidgetProps>,
+) -> Vec<Cell> {
+    let mut occupancy = Occupancy {
+        columns,
+        rows: Vec::new(),
+    };
+    let mut cursor = 0;
+
+    children
+        .map(|props| {
+            let line = |v: usize| v.clamp(1, MAX_GRID_LINE);
+            let span = [props.column_span.clamp(1, columns), line(props.row_span)];
+            let last_column = columns - span[0];
+            let requested_column = props.column.map(|c| (line(c) - 1).min(last_column));
+            let requested_row = props.row.map(|r| line(r) - 1);
+
+            let cell = match (requested_column, requested_row) {
+                (Some(column), Some(row)) => Cell {
+                    start: [column, row],
+                    span,
+                },
+                (Some(column), None) => (0..)
+                    .map(|row| Cell {
+                        start: [column, row],
+                        span,
+                    })
+                    .find(|cell| occupancy.is_free(cell))
+                    .unwrap(),
+                (None, Some(row)) => (0..=last_column)
+                    .map(|column| Cell {
+                        start: [column, row],
+                        span,
+                    })
+                    .find(|cell| occupancy.is_free(cell))
+                    // A full row leaves the widget overlapping the end of it
+                    .unwrap_or(Cell {
+                        start: [last_column, row],
+                        span,
+                    }),
+                (None, None) => {
+                    let cell = (cursor..)
+                        .map(|i| Cell {
+                            start: [i % columns, i / columns],
+                            span,
+                        })
+                        .find(|cell| cell.start[0] <= last_column && occupancy.is_free(cell))
+                        .unwrap();
+                    cursor = cell.start[1] * columns + cell.start[0] + span[0];
+                    cell
+                }
+            };
+
+            occupancy.take(&cell);
+            cell
+        })
+        .collect()
+}
+
+/// Places the children of a grid and sizes its tracks to fit them. Returns the placement
+/// along with the size of the content.
+pub(crate) fn measure_grid(
+    spec: &GridSpec,
+    props: &WidgetProps,
+    children: &[(&UiNode, &Measured)],
+) -> (GridMeasure, Vec2) {
+    let columns = spec.columns.len().max(1);
+    let cells = place_children(columns, children.iter().map(|(ch, _)| &ch.props));
+    let rows = cells
+        .iter()
+        .map(|cell| cell.start[1] + cell.span[1])
+        .max()
+        .unwrap_or(0)
+        .max(spec.rows.len());
+
+    let tracks = |defined: &[Track], count: usize| -> Vec<Track> {
+        (0..count)
+            .map(|i| defined.get(i).copied().unwrap_or(Track::Auto))
+            .collect()
+    };
+    let tracks = [tracks(&spec.columns, columns), tracks(&spec.rows, rows)];
+
+    let gap = props.gap;
+    let natural = [0, 1].map(|a| {
+        let tracks = &tracks[a];
+        let mut natural: Vec<f32> = tracks
+            .iter()
+            .map(|track| match *track {
+                Track::Px(size) => size,
+                Track::Auto | Track::Fr(_) => 0.0,
+            })
+            .collect();
+
+        // Children in a single track size it directly, and the ones spanning several make
+        // up for what they are still missing by growing their flexible tracks evenly
+        let mut by_span: Vec<(&Cell, &Measured)> = cells
+            .iter()
+            .zip(children.iter().map(|(_, measured)| *measured))
+            .collect();
+        by_span.sort_by_key(|(cell, _)| cell.span[a]);
+
+        for (cell, measured) in by_span {
+            let covered = cell.start[a]..cell.start[a] + cell.span[a];
+            let flexible: Vec<usize> = covered
+                .clone()
+                .filter(|&i| !matches!(tracks[i], Track::Px(_)))
+                .collect();
+            if flexible.is_empty() {
+                continue;
+            }
+
+            let current: f32 =
+                natural[covered].iter().sum::<f32>() + gap * (cell.span[a] - 1) as f32;
+            let missing = axis(measured.size, a) - current;
+            if missing > 0.0 {
+                for i in &flexible {
+                    natural[*i] += missing / flexible.len() as f32;
+                }
+            }
+        }
+
+        natural
+    });
+
+    let length =
+        |sizes: &[f32]| sizes.iter().sum::<f32>() + gap * sizes.len().saturating_sub(1) as f32;
+    let size = vec2(length(&natural[0]), length(&natural[1]));
+
+    (
+        GridMeasure {
+            cells,
+            tracks,
+            natural,
+        },
+        size,
+    )
+}
+
+/// Final sizes of the tracks along one axis. Fractional tracks share the space left by the
+/// others according to their weights, without becoming smaller than their content.
+fn track_sizes(tracks: &[Track], natural: &[f32], available: f32, gap: f32) -> Vec<f32> {
+    let gaps = gap * tracks.len().saturating_sub(1) as f32;
+    let fixed: f32 = tracks
+        .iter()
+        .zip(natural)
+        .filter(|(track, _)| !matches!(track, Track::Fr(_)))
+        .map(|(_, size)| size)
+        .sum();
+    let free = (available - gaps - fixed).max(0.0);
+    let total_fr: f32 = tracks
+        .iter()
+        .map(|track| match *track {
+            Track::Fr(weight) => weight,
+            _ => 0.0,
+        })
+        .sum();
+
+    tracks
+        .iter()
+        .zip(natural)
+        .map(|(track, &size)| match *track {
+            Track::Fr(weight) if total_fr > 0.0 => size.max(free * weight / total_fr),
+            _ => size,
+        })
+        .collect()
+}
+
+/// Places the children of a grid in their cells, each according to its
+/// `justify_self` and `align_self`, and stretching it to fill the cell by default.
+pub(crate) fn arrange_grid(
+    props: &WidgetProps,
+    grid: &GridMeasure,
+    children: &[(&UiNode, &Measured)],
+    inner: Vec2,
+) -> Vec<Rect> {
+    let gap = props.gap;
+
+    let positions = [0, 1].map(|a| {
+        let sizes = track_sizes(&grid.tracks[a], &grid.natural[a], axis(inner, a), gap);
+        let mut pos = 0.0;
+        let starts: Vec<f32> = sizes
+            .iter()
+            .map(|size| {
+                let start = pos;
+                pos += size + gap;
+                start
+            })
+            .collect();
+        (starts, sizes)
+    });
+
+    children
+        .iter()
+        .zip(&grid.cells)
+        .map(|(&(child, measured), cell)| {
+            let place = |a: usize| {
+                let (ref starts, ref sizes) = positions[a];
+                let covered = cell.start[a]..cell.start[a] + cell.span[a];
+                let space = sizes[covered].iter().sum::<f32>() + gap * (cell.span[a] - 1) as f32;
+                let align = if a == 0 {
+                    child.props.justify_self
+                } else {
+                    child.props.align_self
+                };
+
+                let (offset, length) = align_in(
+                    align.unwrap_or(Align::Stretch),
+                    space,
+                    &AxisProps::new(&child.props, a),
+                    axis(measured.size, a),
+                );
+                (starts[cell.start[a]] + offset, length)
+            };
+
+            let ((x, width), (y, height)) = (place(0), place(1));
+            Rect::new(vec2(x, y), vec2(width, height))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::calculate_ui_layout;
+    use crate::loader::parse_ui_source;
+    use crate::recording::RecordingRenderer;
+    use crate::ui::Widget;
+
+    #[test]
+    fn huge_cell_properties_are_clamped() {
+        let mut grid = UiNode::new(Widget::Grid(GridSpec {
+            columns: vec![Track::Auto, Track::Auto],
+            rows: Vec::new(),
+        }));
+        let mut child = UiNode::new(Widget::Label("x".to_owned()));
+        child.props.row = Some(usize::MAX);
+        child.props.row_span = usize::MAX;
+        child.props.column = Some(usize::MAX);
+        child.props.column_span = usize::MAX;
+        grid.push(child);
+
+        let layout = calculate_ui_layout(&grid, &mut RecordingRenderer::new(), vec2(100.0, 100.0));
+        assert_eq!(layout.children.len(), 1);
+
+        let props = ast::Properties(vec![ast::Property {
+            name: "row".to_owned(),
+            value: ast::Value::Float(1e30),
+            span: ast::Span::default(),
+        }]);
+        assert_eq!(WidgetProps::from_ast(&props).row, Some(MAX_GRID_LINE));
+    }
+
+    #[test]
+    fn non_finite_tracks_are_rejected() {
+        for track in &[
+            "inf", "infinity", "-inf", "NaN", "inffr", "infpx", "1e40px", "-1px",
+        ] {
+            assert!(Track::parse(track).is_err(), "{}", track);
+        }
+        assert!(parse_tracks("inf 1fr").is_err());
+        assert_eq!(
+            parse_tracks("auto 1.5fr 120px"),
+            Ok(vec![Track::Auto, Track::Fr(1.5), Track::Px(120.0)])
+        );
+    }
+
+    #[test]
+    fn cell_properties_out_of_range_are_reported() {
+        let source = crate::diagnostics::Source::new(
+            "test.grui",
+            "label \"x\" (row: 0, column: 2.5, row_span: 1001, column_span: 1000);",
+        );
+        let nodes = parse_ui_source(&source, ast::FileId(0)).unwrap();
+        let item = match nodes[0] {
+            ast::Node::Item(ref item) => item,
+            _ => unreachable!(),
+        };
+
+        let errors = check_cells(item);
+        let names: Vec<&str> = errors
+            .iter()
+            .map(|(_, message)| message.split('`').nth(1).unwrap())
+            .collect();
+        assert_eq!(names, ["row", "column", "row_span"]);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::ast;
+use crate::binding::DataModel;
+use crate::loader::UiLoader;
+use crate::recording::{DrawCommand, RecordingRenderer};
+use crate::registry::WidgetRegistry;
+use crate::render::Renderer;
+use crate::software::SoftwareRenderer;
+use crate::{FrameSource, InputEvent, UiHost, FONT_PATH, INIT_WINDOW_SIZE};
+
+use ab_glyph::FontVec;
+use glam::vec2;
+use tiny_skia::Pixmap;
+
+use std::path::{Path, PathBuf};
+
+/// Same as the window's clear color
+const BACKGROUND: ast::Color = ast::Color::rgba(77, 77, 82, 255);
+
+/// Largest per-channel difference between golden and rendered pixels still considered equal
+pub const DEFAULT_TOLERANCE: u8 = 2;
+
+/// One step of simulated input. Each step is followed by a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputStep {
+    Move(f32, f32),
+    Press,
+    Release,
+    /// Wheel movement in pixels, as in `InputEvent::Wheel`
+    Wheel(f32, f32),
+    /// Renders a frame without changing the input
+    Wait,
+}
+
+/// A headless render: image size, and the input to simulate before the final frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputScript {
+    pub size: (u32, u32),
+    pub steps: Vec<InputStep>,
+}
+
+impl Default for InputScript {
+    fn default() -> Self {
+        Self {
+            size: INIT_WINDOW_SIZE,
+            steps: Vec::new(),
+        }
+    }
+}
+
+impl InputScript {
+    /// Parses one command per line: `size W H` (before any other command), `move X Y`,
+    /// `press`, `release`, `click` (press and release), `wheel DX DY` or `wait`. `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut script = Self::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let err = |msg: &str| format!("line {}: {}", i + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let numbers = || -> Result<Vec<f32>, String> {
+                words[1..]
+                    .iter()
+                    .map(|w| w.parse::<f32>().map_err(|_| err("expected a number")))
+                    .collect()
+            };
+
+            match (words[0], words.len()) {
+                ("size", 3) if script.steps.is_empty() => {
+                    let n = numbers()?;
+                    if n[0] < 1.0 || n[1] < 1.0 {
+                        return Err(err("size must be at least 1x1"));
+                    }
+                    script.size = (n[0] as u32, n[1] as u32);
+                }
+                ("size", 3) => return Err(err("`size` must come before any input")),
+                ("move", 3) => {
+                    let n = numbers()?;
+                    script.steps.push(InputStep::Move(n[0], n[1]));
+                }
+                ("wheel", 3) => {
+                    let n = numbers()?;
+                    script.steps.push(InputStep::Wheel(n[0], n[1]));
+                }
+                ("press", 1) => script.steps.push(InputStep::Press),
+                ("release", 1) => script.steps.push(InputStep::Release),
+                ("click", 1) => {
+                    script.steps.push(InputStep::Press);
+                    script.steps.push(InputStep::Release);
+                }
+                ("wait", 1) => script.steps.push(InputStep::Wait),
+                (cmd, _) => return Err(err(&format!("invalid command `{}`", cmd))),
+            }
+        }
+
+        Ok(script)
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Loads the font the UI is drawn with, from the package directory so that it is found from
+/// any working directory.
+pub fn load_font() -> Result<FontVec, String> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(FONT_PATH);
+    let data = std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    FontVec::try_from_vec(data).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Runs the UI in a .grui file without a window, one frame per input step and one more at
+/// the end, calling `begin_frame` before each. Diagnostics are drawn as in the window.
+fn run_script<R: Renderer>(
+    path: &Path,
+    script: &InputScript,
+    renderer: &mut R,
+    mut begin_frame: impl FnMut(&mut R),
+) {
+    let registry = WidgetRegistry::default();
+    let model: &dyn DataModel = &();
+
+    let mut loader = UiLoader::new(path);
+    loader.poll(&registry);
+
+    let (width, height) = script.size;
+    let mut host = UiHost::new(vec2(width as f32, height as f32));
+
+    let source = FrameSource {
+        ast: loader.ast(),
+        diagnostics: loader.diagnostics(),
+        registry: &registry,
+        model,
+    };
+
+    for step in script.steps.iter().chain(Some(&InputStep::Wait)) {
+        match *step {
+            InputStep::Move(x, y) => host.handle_event(InputEvent::MouseMove(vec2(x, y))),
+            InputStep::Press => host.handle_event(InputEvent::MouseDown),
+            InputStep::Release => host.handle_event(InputEvent::MouseUp),
+            InputStep::Wheel(dx, dy) => host.handle_event(InputEvent::Wheel(vec2(dx, dy))),
+            InputStep::Wait => (),
+        }
+
+        begin_frame(renderer);
+        host.frame(renderer, &source, &mut |_| ());
+    }
+}
+
+/// Renders a .grui file to an image, as it looks after the input in `script`.
+pub fn render_ui(path: &Path, script: &InputScript, font: FontVec) -> Result<Pixmap, String> {
+    let (width, height) = script.size;
+    let mut renderer = SoftwareRenderer::new(width, height, font)
+        .ok_or_else(|| format!("invalid image size {}x{}", width, height))?;
+
+    run_script(path, script, &mut renderer, |r| r.clear(BACKGROUND));
+
+    Ok(renderer.pixmap().clone())
+}
+
+/// The draw commands of the last frame of a .grui file, after the input in `script`.
+pub fn record_ui(path: &Path, script: &InputScript) -> Vec<DrawCommand> {
+    let mut renderer = RecordingRenderer::new();
+    run_script(path, script, &mut renderer, |r| r.commands.clear());
+    renderer.commands
+}
+
+fn save_png(pixmap: &Pixmap, path: &Path) -> Result<(), String> {
+    pixmap
+        .save_png(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Splits `FILE... [--input SCRIPT]` arguments, loading the script if one is given.
+fn parse_script_args(args: &[String]) -> Result<(Vec<&String>, InputScript), String> {
+    let mut positional = Vec::new();
+    let mut script_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => script_path = args.next(),
+            _ => positional.push(arg),
+        }
+    }
+
+    let script = match script_path {
+        Some(p) => InputScript::load(Path::new(p))?,
+        None => InputScript::default(),
+    };
+
+    Ok((positional, script))
+}
+
+/// `grui render FILE.grui OUT.png [--input SCRIPT]`. Returns the process exit code.
+pub fn run_render(args: &[String]) -> i32 {
+    let result =
+        parse_script_args(args).and_then(|(positional, script)| match positional.as_slice() {
+            [ui, out] => {
+                let pixmap = render_ui(Path::new(ui.as_str()), &script, load_font()?)?;
+                save_png(&pixmap, Path::new(out.as_str())).map(|()| 0)
+            }
+            _ => {
+                eprintln!("usage: grui render FILE.grui OUT.png [--input SCRIPT]");
+                Ok(2)
+            }
+        });
+
+    result.unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        1
+    })
+}
+
+/// `grui draw-commands FILE.grui [--input SCRIPT]`: prints what the last frame draws, one
+/// command per line. Text is measured without a font; see `RecordingRenderer`.
+pub fn run_draw_commands(args: &[String]) -> i32 {
+    let result = parse_script_args(args).map(|(positional, script)| match positional.as_slice() {
+        [ui] => {
+            for command in record_ui(Path::new(ui.as_str()), &script) {
+                println!("{}", command);
+            }
+            0
+        }
+        _ => {
+            eprintln!("usage: grui draw-commands FILE.grui [--input SCRIPT]");
+            2
+        }
+    });
+
+    result.unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        1
+    })
+}
+
+/// How far a rendered image is from its golden image
+struct ImageDiff {
+    mismatched_pixels: usize,
+    max_difference: u8,
+    /// The golden image dimmed, with mismatched pixels in red
+    visualization: Pixmap,
+}
+
+/// Returns `None` if the images have different sizes.
+fn compare_images(actual: &Pixmap, expected: &Pixmap, tolerance: u8) -> Option<ImageDiff> {
+    if actual.width() != expected.width() || actual.height() != expected.height() {
+        return None;
+    }
+
+    let mut visualization = expected.clone();
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+
+    for (i, (a, e)) in actual
+        .data()
+        .chunks(4)
+        .zip(expected.data().chunks(4))
+        .enumerate()
+    {
+        let difference = a
+            .iter()
+            .zip(e)
+            .map(|(a, e)| (*a as i32 - *e as i32).unsigned_abs() as u8)
+            .max()
+            .unwrap();
+
+        max_difference = max_difference.max(difference);
+
+        let pixel = &mut visualization.data_mut()[i * 4..i * 4 + 4];
+        if difference > tolerance {
+            mismatched_pixels += 1;
+            pixel.copy_from_slice(&[255, 0, 0, 255]);
+        } else {
+            for c in &mut pixel[..3] {
+                *c /= 4;
+            }
+        }
+    }
+
+    Some(ImageDiff {
+        mismatched_pixels,
+        max_difference,
+        visualization,
+    })
+}
+
+/// `grui golden DIR [--bless] [--tolerance N]`: renders every `NAME.grui` in the directory,
+/// with input from `NAME.input` if present, and compares the result against `NAME.png`.
+/// Failures leave `NAME.actual.png` and `NAME.diff.png` next to the golden image.
+/// With `--bless`, the golden images are (re)written instead.
+pub fn run_golden(args: &[String]) -> i32 {
+    let mut positional = Vec::new();
+    let mut bless = false;
+    let mut tolerance = DEFAULT_TOLERANCE;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            "--tolerance" => match args.next().and_then(|t| t.parse().ok()) {
+                Some(t) => tolerance = t,
+                None => {
+                    eprintln!("error: --tolerance expects a number between 0 and 255");
+                    return 2;
+                }
+            },
+            _ => positional.push(arg),
+        }
+    }
+
+    let dir = match positional.as_slice() {
+        [dir] => PathBuf::from(dir),
+        _ => {
+            eprintln!("usage: grui golden DIR [--bless] [--tolerance N]");
+            return 2;
+        }
+    };
+
+    let cases = match golden_cases(&dir) {
+        Ok(cases) => cases,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 2;
+        }
+    };
+
+    let mut failures = 0;
+
+    for case in &cases {
+        match run_golden_case(case, bless, tolerance) {
+            Ok(()) => println!("ok      {}", case.display()),
+            Err(err) => {
+                println!("FAILED  {}: {}", case.display(), err);
+                failures += 1;
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", cases.len() - failures, failures);
+
+    if failures > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// The `NAME.grui` files in `dir`, sorted by name
+pub fn golden_cases(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut cases: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "grui"))
+        .collect();
+    cases.sort();
+    Ok(cases)
+}
+
+/// Renders one case of `run_golden`, and compares it against its golden image, or writes
+/// the golden image with `bless`.
+pub fn run_golden_case(case: &Path, bless: bool, tolerance: u8) -> Result<(), String> {
+    let script_path = case.with_extension("input");
+    let script = if script_path.exists() {
+        InputScript::load(&script_path)?
+    } else {
+        InputScript::default()
+    };
+
+    let actual = render_ui(case, &script, load_font()?)?;
+    let golden_path = case.with_extension("png");
+    let actual_path = case.with_extension("actual.png");
+    let diff_path = case.with_extension("diff.png");
+
+    if bless {
+        let _ = std::fs::remove_file(&actual_path);
+        let _ = std::fs::remove_file(&diff_path);
+        return save_png(&actual, &golden_path);
+    }
+
+    let expected = Pixmap::load_png(&golden_path).map_err(|e| {
+        format!(
+            "cannot load golden image {}: {}; run with --bless to create it",
+            golden_path.display(),
+            e
+        )
+    })?;
+
+    let failure = match compare_images(&actual, &expected, tolerance) {
+        None => format!(
+            "image is {}x{}, golden image is {}x{}",
+            actual.width(),
+            actual.height(),
+            expected.width(),
+            expected.height()
+        ),
+        Some(diff) if diff.mismatched_pixels > 0 => {
+            save_png(&diff.visualization, &diff_path)?;
+            format!(
+                "{} pixels differ, by up to {}; see {}",
+                diff.mismatched_pixels,
+                diff.max_difference,
+                diff_path.display()
+            )
+        }
+        Some(_) => {
+            let _ = std::fs::remove_file(&actual_path);
+            let _ = std::fs::remove_file(&diff_path);
+            return Ok(());
+        }
+    };
+
+    save_png(&actual, &actual_path)?;
+    Err(failure)
+}
@@ -0,0 +1,958 @@
+use crate::grid::{arrange_grid, measure_grid, GridMeasure, GridSpec};
+use crate::render::{Rect, TextMeasure};
+use crate::ui::{GenericWidget, UiNode, Widget, WidgetId, WidgetProps, WidgetUid};
+
+use glam::{vec2, Vec2};
+
+use std::collections::HashMap;
+use std::mem::{discriminant, Discriminant};
+use std::rc::Rc;
+
+/// Font sizes widgets draw their text with, and measure it at
+pub const LABEL_FONT_SIZE: f32 = 18.0;
+pub const BUTTON_FONT_SIZE: f32 = 20.0;
+
+/// Horizontal and vertical space around the text of buttons, unless set with `padding`
+const BUTTON_PADDING: (f32, f32) = (12.0, 2.0);
+
+/// A `width` or `height`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Size {
+    Px(f32),
+    /// Relative to the space inside the parent, such as `100%` to fill it
+    Percent(f32),
+}
+
+/// How a container places its children along its main axis, when they don't fill it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Justify {
+    Start,
+    Center,
+    End,
+    /// The leftover space is split evenly between the children
+    SpaceBetween,
+}
+
+impl Justify {
+    pub const KEYWORDS: &'static [&'static str] = &["start", "center", "end", "space_between"];
+
+    pub fn from_keyword(s: &str) -> Option<Self> {
+        match s {
+            "start" => Some(Justify::Start),
+            "center" => Some(Justify::Center),
+            "end" => Some(Justify::End),
+            "space_between" => Some(Justify::SpaceBetween),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Justify::Start => "start",
+            Justify::Center => "center",
+            Justify::End => "end",
+            Justify::SpaceBetween => "space_between",
+        }
+    }
+}
+
+/// How a container places its children across its main axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    /// Children without a fixed size are made as large as the container
+    Stretch,
+}
+
+impl Align {
+    pub const KEYWORDS: &'static [&'static str] = &["start", "center", "end", "stretch"];
+
+    pub fn from_keyword(s: &str) -> Option<Self> {
+        match s {
+            "start" => Some(Align::Start),
+            "center" => Some(Align::Center),
+            "end" => Some(Align::End),
+            "stretch" => Some(Align::Stretch),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Align::Start => "start",
+            Align::Center => "center",
+            Align::End => "end",
+            Align::Stretch => "stretch",
+        }
+    }
+}
+
+/// Sizes and positions of widgets, mirroring the shape of the `UiNode` tree.
+/// Offsets are relative to the parent.
+#[derive(Debug, Clone)]
+pub struct LayoutTree {
+    pub extent: Vec2,
+    pub offset: Vec2,
+    /// Drawing order among the siblings, from `z_index`: higher is drawn on top
+    pub z: i32,
+    /// Size of the scrolled content of scroll containers, which may be larger than `extent`
+    pub content_extent: Option<Vec2>,
+    /// Shared with the `LayoutCache` the tree comes from, if any
+    pub children: Vec<Rc<LayoutTree>>,
+}
+
+/// Horizontal and vertical space between the edges of a widget and its content
+pub fn padding(widget: &Widget, props: &WidgetProps) -> Vec2 {
+    if let Some(padding) = props.padding {
+        return vec2(padding, padding);
+    }
+
+    let draws_background = match widget {
+        Widget::Button(_) => true,
+        Widget::Generic(GenericWidget { draw_style, .. }) => *draw_style == "button",
+        _ => false,
+    };
+
+    if draws_background {
+        vec2(BUTTON_PADDING.0, BUTTON_PADDING.1)
+    } else {
+        Vec2::zero()
+    }
+}
+
+/// Size of multi-line text, rounded up to whole pixels
+pub fn measure_text(measure: &mut dyn TextMeasure, text: &str, size: f32) -> Vec2 {
+    let line_height = measure.text_metrics(size).line_height;
+    let lines = text.split('\n');
+    let line_count = lines.clone().count();
+    let width = lines
+        .map(|line| measure.text_width(line, size))
+        .fold(0.0, f32::max);
+
+    vec2(width.ceil(), (line_height * line_count as f32).ceil())
+}
+
+/// Index of the axis a container stacks its children along: 0 for x, 1 for y,
+/// or `None` for widgets which don't lay out children.
+fn main_axis(widget: &Widget) -> Option<usize> {
+    match widget {
+        Widget::Horizontal => Some(0),
+        Widget::Vertical | Widget::Scroll | Widget::Generic(_) => Some(1),
+        Widget::Button(_) | Widget::Label(_) | Widget::Grid(_) | Widget::Stack => None,
+    }
+}
+
+pub(crate) fn axis(v: Vec2, axis: usize) -> f32 {
+    if axis == 0 {
+        v.x()
+    } else {
+        v.y()
+    }
+}
+
+/// A vector with `main` along the given axis, and `cross` along the other one
+pub(crate) fn from_axes(main: f32, cross: f32, axis: usize) -> Vec2 {
+    if axis == 0 {
+        vec2(main, cross)
+    } else {
+        vec2(cross, main)
+    }
+}
+
+/// The size properties of a widget along one axis
+pub(crate) struct AxisProps {
+    size: Option<Size>,
+    min: Option<f32>,
+    max: Option<f32>,
+}
+
+impl AxisProps {
+    pub(crate) fn new(props: &WidgetProps, axis: usize) -> Self {
+        if axis == 0 {
+            Self {
+                size: props.width,
+                min: props.min_width,
+                max: props.max_width,
+            }
+        } else {
+            Self {
+                size: props.height,
+                min: props.min_height,
+                max: props.max_height,
+            }
+        }
+    }
+
+    /// Applies the limits, with the minimum winning if they conflict
+    fn clamp(&self, size: f32) -> f32 {
+        size.min(self.max.unwrap_or(f32::INFINITY))
+            .max(self.min.unwrap_or(0.0))
+    }
+
+    /// Size given by the properties, if it is known without the parent's size
+    fn fixed(&self) -> Option<f32> {
+        match self.size {
+            Some(Size::Px(size)) => Some(self.clamp(size)),
+            _ => None,
+        }
+    }
+}
+
+/// Position and size of a child along one axis of the `space` its parent gives it. Children
+/// with a fixed size keep their natural one, and percentages are relative to `space`.
+pub(crate) fn align_in(align: Align, space: f32, props: &AxisProps, natural: f32) -> (f32, f32) {
+    let size = match props.size {
+        Some(Size::Percent(pct)) => props.clamp(space * pct / 100.0),
+        Some(Size::Px(_)) => natural,
+        None if align == Align::Stretch => props.clamp(space),
+        None => natural,
+    };
+
+    let offset = match align {
+        Align::Start | Align::Stretch => 0.0,
+        Align::Center => (space - size) * 0.5,
+        Align::End => space - size,
+    };
+
+    (offset, size)
+}
+
+/// Natural size of a widget, before the parent distributes its space, and of its children
+pub(crate) struct Measured {
+    /// What the widget itself was measured from, for telling whether it changed since the
+    /// last pass
+    key: MeasureKey,
+    pub(crate) size: Vec2,
+    /// Size of the text or children, without padding or the widget's own size properties
+    pub(crate) content: Vec2,
+    pub(crate) children: Vec<Rc<Measured>>,
+    /// Placement of the children, for grids
+    pub(crate) grid: Option<GridMeasure>,
+}
+
+/// The parts of a widget, besides its children, which its layout depends on
+struct MeasureKey {
+    kind: Discriminant<Widget>,
+    /// Of labels and buttons
+    text: String,
+    /// Of generic widgets
+    draw_style: &'static str,
+    grid: Option<GridSpec>,
+    props: WidgetProps,
+}
+
+impl MeasureKey {
+    fn new(node: &UiNode) -> Self {
+        let mut key = MeasureKey {
+            kind: discriminant(&node.widget),
+            text: String::new(),
+            draw_style: "",
+            grid: None,
+            props: node.props.clone(),
+        };
+
+        match node.widget {
+            Widget::Label(ref s) | Widget::Button(ref s) => key.text = s.clone(),
+            Widget::Generic(ref generic) => key.draw_style = generic.draw_style,
+            Widget::Grid(ref spec) => key.grid = Some(spec.clone()),
+            Widget::Horizontal | Widget::Vertical | Widget::Scroll | Widget::Stack => (),
+        }
+
+        key
+    }
+
+    /// Compares without allocating, as this runs for every widget in every pass
+    fn matches(&self, node: &UiNode) -> bool {
+        let widget = match node.widget {
+            Widget::Label(ref s) | Widget::Button(ref s) => *s == self.text,
+            Widget::Generic(ref generic) => generic.draw_style == self.draw_style,
+            Widget::Grid(ref spec) => self.grid.as_ref() == Some(spec),
+            Widget::Horizontal | Widget::Vertical | Widget::Scroll | Widget::Stack => true,
+        };
+
+        self.kind == discriminant(&node.widget) && widget && self.props == node.props
+    }
+}
+
+/// Layout results of a widget and its children from the previous pass, in the same shape as
+/// the `UiNode` tree
+#[derive(Default)]
+struct CacheNode {
+    measured: Option<Rc<Measured>>,
+    /// The layout, and the size it was made for
+    arranged: Option<(Vec2, Rc<LayoutTree>)>,
+    /// In the same order as the children of the widget
+    children: Vec<(WidgetId, CacheNode)>,
+}
+
+impl CacheNode {
+    /// Pairs the cached children up with the widget's children by id, dropping the ones which
+    /// are gone. Children mostly stay in place, so they are matched in order until the first
+    /// one which doesn't.
+    fn match_children(&mut self, node: &UiNode) {
+        let unmoved = self.children.len() == node.children.len()
+            && self
+                .children
+                .iter()
+                .zip(&node.children)
+                .all(|((old_id, _), (id, _))| old_id == id);
+        if unmoved {
+            return;
+        }
+
+        let mut old = std::mem::take(&mut self.children).into_iter();
+        let mut moved: Option<HashMap<WidgetId, CacheNode>> = None;
+
+        self.children = node
+            .children
+            .iter()
+            .map(|&(id, _)| {
+                let cache = match moved {
+                    Some(ref mut moved) => moved.remove(&id),
+                    None => match old.next() {
+                        Some((old_id, cache)) if old_id == id => Some(cache),
+                        Some(other) => {
+                            let mut rest: HashMap<_, _> =
+                                std::iter::once(other).chain(&mut old).collect();
+                            let cache = rest.remove(&id);
+                            moved = Some(rest);
+                            cache
+                        }
+                        None => None,
+                    },
+                };
+                (id, cache.unwrap_or_default())
+            })
+            .collect();
+    }
+}
+
+/// How much work a layout pass did, and how much it reused from the previous one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LayoutStats {
+    /// Widgets looked at, either to check that they didn't change or to lay them out again.
+    /// Only the children of widgets which changed are looked at.
+    pub visited: usize,
+    /// Widgets whose natural size was computed again, because they or their subtree changed
+    pub measured: usize,
+    /// Widgets laid out again, because they changed or were given a different size
+    pub arranged: usize,
+}
+
+/// Layout results kept from one frame to the next, so that only the widgets which changed are
+/// laid out again. Widgets are matched by their ids, and compared with the content and
+/// properties they were last measured with.
+///
+/// Subtrees which `UiNode::reconcile` found unchanged are not looked into, unless they have
+/// to be arranged in a different size. Their roots still are, when a sibling changed: the
+/// parent checks each of its children, and places all of them again. A change therefore
+/// costs time in proportion to the number of children of each of its ancestors, which adds
+/// up in long flat lists.
+///
+/// This relies on the tree being laid out by the same cache after every `reconcile`, as
+/// `UiHost` does. Nodes which were never reconciled count as changed, so trees built and
+/// edited by hand are always compared in full.
+///
+/// Text is assumed to measure the same in every pass; call `clear` after changing fonts.
+#[derive(Default)]
+pub struct LayoutCache {
+    root: CacheNode,
+    stats: LayoutStats,
+}
+
+impl LayoutCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lays out `root` like `calculate_ui_layout`, reusing what is still valid from the
+    /// previous call. Parts of the old tree which are not in `root` anymore are dropped.
+    pub fn layout(
+        &mut self,
+        root: &UiNode,
+        text: &mut dyn TextMeasure,
+        available: Vec2,
+    ) -> Rc<LayoutTree> {
+        self.stats = LayoutStats::default();
+        let mut pass = Pass {
+            text,
+            stats: &mut self.stats,
+        };
+
+        let measured = measure(root, &mut self.root, &mut pass);
+        arrange(
+            root,
+            &measured,
+            available,
+            Vec2::zero(),
+            &mut self.root,
+            &mut pass,
+        )
+    }
+
+    /// What the last `layout` call did
+    pub fn stats(&self) -> LayoutStats {
+        self.stats
+    }
+
+    pub fn clear(&mut self) {
+        self.root = CacheNode::default();
+    }
+}
+
+struct Pass<'a, 'b> {
+    text: &'a mut dyn TextMeasure,
+    stats: &'b mut LayoutStats,
+}
+
+/// Sizes a widget to fit its text or children, or to its fixed `width` and `height`.
+/// Percentages are left to `arrange`, which knows the size of the parent.
+///
+/// Children are measured first, and the widget is then only measured again if it or any of
+/// its children changed since the last pass. Unchanged subtrees are not looked into.
+fn measure(node: &UiNode, cache: &mut CacheNode, pass: &mut Pass) -> Rc<Measured> {
+    pass.stats.visited += 1;
+    if !node.changed {
+        if let Some(ref measured) = cache.measured {
+            return measured.clone();
+        }
+    }
+
+    cache.match_children(node);
+    for ((_, child), (_, child_cache)) in node.children.iter().zip(&mut cache.children) {
+        measure(child, child_cache, pass);
+    }
+
+    if let Some(ref measured) = cache.measured {
+        // Children which changed were measured again, into new `Rc`s
+        let same_children = measured.children.len() == cache.children.len()
+            && measured
+                .children
+                .iter()
+                .zip(&cache.children)
+                .all(|(old, (_, child))| {
+                    child
+                        .measured
+                        .as_ref()
+                        .is_some_and(|new| Rc::ptr_eq(old, new))
+                });
+
+        if same_children && measured.key.matches(node) {
+            return measured.clone();
+        }
+    }
+    pass.stats.measured += 1;
+
+    let children: Vec<Rc<Measured>> = cache
+        .children
+        .iter()
+        .map(|(_, child)| child.measured.clone().unwrap())
+        .collect();
+
+    // Positioned children don't take any room in their parent
+    let in_flow: Vec<(&UiNode, &Measured)> = node
+        .children
+        .iter()
+        .map(|(_, child)| child)
+        .zip(children.iter().map(|ch| &**ch))
+        .filter(|(child, _)| !child.props.is_positioned())
+        .collect();
+
+    let padding = padding(&node.widget, &node.props);
+    let mut grid = None;
+    let content = match (&node.widget, main_axis(&node.widget)) {
+        (Widget::Button(s), _) => measure_text(pass.text, s, BUTTON_FONT_SIZE),
+        (Widget::Label(s), _) => measure_text(pass.text, s, LABEL_FONT_SIZE),
+        (Widget::Grid(spec), _) => {
+            let (measured, content) = measure_grid(spec, &node.props, &in_flow);
+            grid = Some(measured);
+            content
+        }
+        (Widget::Stack, _) => in_flow
+            .iter()
+            .fold(Vec2::zero(), |size, (_, ch)| size.max(ch.size)),
+        (_, Some(main)) => {
+            let gaps = node.props.gap * in_flow.len().saturating_sub(1) as f32;
+            let length: f32 = in_flow.iter().map(|(_, ch)| axis(ch.size, main)).sum();
+            let thickness = in_flow
+                .iter()
+                .map(|(_, ch)| axis(ch.size, 1 - main))
+                .fold(0.0, f32::max);
+            from_axes(length + gaps, thickness, main)
+        }
+        (_, None) => Vec2::zero(),
+    };
+
+    let natural = content + padding * 2.0;
+    let size = |i: usize| {
+        let props = AxisProps::new(&node.props, i);
+        props
+            .fixed()
+            .unwrap_or_else(|| props.clamp(axis(natural, i)))
+    };
+
+    let measured = Rc::new(Measured {
+        key: MeasureKey::new(node),
+        size: vec2(size(0), size(1)),
+        content,
+        children,
+        grid,
+    });
+
+    cache.measured = Some(measured.clone());
+    // The old layout was made for the old content
+    cache.arranged = None;
+    measured
+}
+
+/// Lays out `node` and its children in the `size` its parent gave it, at `offset` from the
+/// parent. The previous layout is reused if the widget was given the same size, and hasn't
+/// been measured again since.
+fn arrange(
+    node: &UiNode,
+    measured: &Measured,
+    size: Vec2,
+    offset: Vec2,
+    cache: &mut CacheNode,
+    pass: &mut Pass,
+) -> Rc<LayoutTree> {
+    if let Some((cached_size, ref mut layout)) = cache.arranged {
+        if cached_size == size {
+            if layout.offset != offset {
+                Rc::make_mut(layout).offset = offset;
+            }
+            return layout.clone();
+        }
+    }
+    pass.stats.arranged += 1;
+
+    let padding = padding(&node.widget, &node.props);
+    let mut inner = size - padding * 2.0;
+    let mut content_extent = None;
+    if let Widget::Scroll = node.widget {
+        // The content keeps its natural size, and the parts which don't fit are scrolled to
+        inner = inner.max(measured.content);
+        content_extent = Some(inner + padding * 2.0);
+    }
+
+    let children: Vec<(&UiNode, &Measured)> = node
+        .children
+        .iter()
+        .map(|(_, child)| child)
+        .zip(measured.children.iter().map(|ch| &**ch))
+        .collect();
+    let in_flow: Vec<(&UiNode, &Measured)> = children
+        .iter()
+        .copied()
+        .filter(|(child, _)| !child.props.is_positioned())
+        .collect();
+
+    // Where the children in flow go, inside the padding
+    let in_flow = match (&node.widget, main_axis(&node.widget)) {
+        (Widget::Grid(_), _) => match measured.grid {
+            Some(ref grid) => arrange_grid(&node.props, grid, &in_flow, inner),
+            None => Vec::new(),
+        },
+        (Widget::Stack, _) => arrange_stack(&node.props, &in_flow, inner),
+        (_, Some(main)) => arrange_box(&node.props, main, &in_flow, inner),
+        (_, None) => Vec::new(),
+    };
+    let mut in_flow = in_flow.into_iter();
+
+    // The cache's children were matched to the widget's when it was measured
+    let children = children
+        .into_iter()
+        .zip(&mut cache.children)
+        .map(|((child, measured), (_, child_cache))| {
+            let rect = if child.props.is_positioned() {
+                arrange_positioned(child, measured, size)
+            } else {
+                let rect = in_flow.next().unwrap();
+                Rect::new(rect.pos + padding, rect.size)
+            };
+
+            arrange(child, measured, rect.size, rect.pos, child_cache, pass)
+        })
+        .collect();
+
+    let layout = Rc::new(LayoutTree {
+        extent: size,
+        offset,
+        z: node.props.z_index,
+        content_extent,
+        children,
+    });
+
+    cache.arranged = Some((size, layout.clone()));
+    layout
+}
+
+/// Stacks `children` along the `main` axis, sharing out the space along it with `grow` and
+/// `shrink`, and aligning them across it.
+fn arrange_box(
+    props: &WidgetProps,
+    main: usize,
+    children: &[(&UiNode, &Measured)],
+    inner: Vec2,
+) -> Vec<Rect> {
+    let cross = 1 - main;
+    let (inner_main, inner_cross) = (axis(inner, main), axis(inner, cross));
+
+    // Sizes along the main axis, starting from the natural ones
+    let mut lengths: Vec<f32> = children
+        .iter()
+        .map(|(child, measured)| {
+            let child_props = AxisProps::new(&child.props, main);
+            match child_props.size {
+                Some(Size::Percent(pct)) => child_props.clamp(inner_main * pct / 100.0),
+                _ => axis(measured.size, main),
+            }
+        })
+        .collect();
+
+    let gaps = props.gap * children.len().saturating_sub(1) as f32;
+    let free = inner_main - gaps - lengths.iter().sum::<f32>();
+
+    // Leftover space goes to the children which grow, and missing space is taken from the
+    // ones which shrink, in proportion to their size as well as their weight
+    let total_grow: f32 = children.iter().map(|(ch, _)| ch.props.grow).sum();
+    let total_shrink: f32 = children
+        .iter()
+        .zip(&lengths)
+        .map(|((ch, _), length)| ch.props.shrink * length)
+        .sum();
+
+    for ((child, _), length) in children.iter().zip(&mut lengths) {
+        let flexed = if free > 0.0 && total_grow > 0.0 {
+            *length + free * child.props.grow / total_grow
+        } else if free < 0.0 && total_shrink > 0.0 {
+            *length + free * child.props.shrink * *length / total_shrink
+        } else {
+            *length
+        };
+
+        *length = AxisProps::new(&child.props, main).clamp(flexed.max(0.0));
+    }
+
+    let free = inner_main - gaps - lengths.iter().sum::<f32>();
+    let (mut pos, spacing) = match props.justify {
+        Justify::Start => (0.0, props.gap),
+        Justify::Center => (free * 0.5, props.gap),
+        Justify::End => (free, props.gap),
+        Justify::SpaceBetween if children.len() > 1 => {
+            (0.0, props.gap + free.max(0.0) / (children.len() - 1) as f32)
+        }
+        Justify::SpaceBetween => (0.0, props.gap),
+    };
+
+    children
+        .iter()
+        .zip(lengths)
+        .map(|((child, measured), length)| {
+            let (cross_pos, thickness) = align_in(
+                child.props.align_self.unwrap_or(props.align),
+                inner_cross,
+                &AxisProps::new(&child.props, cross),
+                axis(measured.size, cross),
+            );
+
+            let rect = Rect::new(
+                from_axes(pos, cross_pos, main),
+                from_axes(length, thickness, main),
+            );
+            pos += length + spacing;
+            rect
+        })
+        .collect()
+}
+
+/// Puts `children` on top of each other, each placed in the whole of the stack by its
+/// `justify_self` and `align_self`, or by the stack's `align` on both axes.
+fn arrange_stack(props: &WidgetProps, children: &[(&UiNode, &Measured)], inner: Vec2) -> Vec<Rect> {
+    children
+        .iter()
+        .map(|(child, measured)| {
+            let place = |a: usize| {
+                let align = if a == 0 {
+                    child.props.justify_self
+                } else {
+                    child.props.align_self
+                };
+
+                align_in(
+                    align.unwrap_or(props.align),
+                    axis(inner, a),
+                    &AxisProps::new(&child.props, a),
+                    axis(measured.size, a),
+                )
+            };
+
+            let ((x, width), (y, height)) = (place(0), place(1));
+            Rect::new(vec2(x, y), vec2(width, height))
+        })
+        .collect()
+}
+
+/// Places a child by its `left`, `top`, `right` and `bottom` offsets from the edges of its
+/// parent, whose size is `parent`. A child anchored to both edges along an axis, and without
+/// a size along it, is stretched between them. Otherwise it keeps its natural size, and is
+/// placed at the start if it isn't anchored at all.
+fn arrange_positioned(child: &UiNode, measured: &Measured, parent: Vec2) -> Rect {
+    let props = &child.props;
+    let place = |a: usize| {
+        let (start, end) = if a == 0 {
+            (props.left, props.right)
+        } else {
+            (props.top, props.bottom)
+        };
+        let axis_props = AxisProps::new(props, a);
+        let space = axis(parent, a);
+
+        let length = match (axis_props.size, start, end) {
+            (Some(Size::Percent(pct)), _, _) => axis_props.clamp(space * pct / 100.0),
+            (None, Some(start), Some(end)) => axis_props.clamp(space - start - end),
+            _ => axis(measured.size, a),
+        };
+        let pos = match (start, end) {
+            (Some(start), _) => start,
+            (None, Some(end)) => space - end - length,
+            (None, None) => 0.0,
+        };
+
+        (pos, length)
+    };
+
+    let ((x, width), (y, height)) = (place(0), place(1));
+    Rect::new(vec2(x, y), vec2(width, height))
+}
+
+/// Lays out `root` and everything in it to fill `available`, normally the window's inner size.
+///
+/// Containers stack their children along their main axis, `gap` apart and placed according
+/// to `justify`; `align` places them across it. Children start at their natural size, which
+/// fits their text or children, unless they have a fixed `width` or `height`, or one in
+/// percent of the container. Leftover space along the main axis is then shared between the
+/// children according to their `grow` weights, and missing space is taken from them
+/// according to `shrink`. The `min_` and `max_` size limits are applied last.
+///
+/// Scroll containers stack their children like `vertical` ones, in an area at least as large
+/// as their content, which is shown through the container's own size.
+///
+/// Grids place their children in cells instead, sizing `auto` tracks to fit them and
+/// sharing the leftover space between `fr` tracks, and stacks put them all over each other.
+///
+/// Children with a `left`, `top`, `right` or `bottom` offset are taken out of their parent's
+/// layout, and placed by those offsets from the parent's edges instead.
+///
+/// Everything is laid out from scratch; see `LayoutCache` for laying out a tree repeatedly.
+pub fn calculate_ui_layout(
+    root: &UiNode,
+    text: &mut dyn TextMeasure,
+    available: Vec2,
+) -> LayoutTree {
+    let layout = LayoutCache::new().layout(root, text, available);
+    Rc::try_unwrap(layout).unwrap_or_else(|layout| (*layout).clone())
+}
+
+pub(crate) struct FlattenedWidgetNode<'a> {
+    pub(crate) uid: WidgetUid,
+    pub(crate) widget: &'a mut Widget,
+    pub(crate) props: &'a WidgetProps,
+    pub(crate) subtree_size: usize,
+}
+
+fn flatten_widgets_inner<'a>(
+    ui: &'a mut UiNode,
+    uid: &WidgetUid,
+    result: &mut Vec<FlattenedWidgetNode<'a>>,
+) {
+    let index = result.len();
+
+    result.push(FlattenedWidgetNode {
+        uid: uid.clone(),
+        widget: &mut ui.widget,
+        props: &ui.props,
+        subtree_size: 0,
+    });
+
+    for item in ui.children.iter_mut() {
+        let mut uid = uid.clone();
+        uid.0.push(item.0);
+
+        flatten_widgets_inner(&mut item.1, &uid, result);
+    }
+
+    result[index].subtree_size = result.len() - index - 1;
+}
+
+pub(crate) fn flatten_widgets<'a>(ui: &'a mut UiNode) -> Vec<FlattenedWidgetNode<'a>> {
+    let mut result = Vec::new();
+    flatten_widgets_inner(ui, &WidgetUid(Vec::new()), &mut result);
+    result
+}
+
+#[derive(Debug)]
+pub(crate) struct FlattenedLayout {
+    pub(crate) offset: Vec2,
+    pub(crate) extent: Vec2,
+    /// Drawing order, adding up the `z_index` of the widget and its ancestors
+    pub(crate) z: i32,
+    pub(crate) content_extent: Option<Vec2>,
+    /// The part of the window the widget is visible in, when inside scroll containers
+    pub(crate) clip: Option<Rect>,
+}
+
+impl FlattenedLayout {
+    /// Whether `point` is over the widget, and not over a part of it which is clipped away
+    pub(crate) fn contains(&self, point: Vec2) -> bool {
+        Rect::new(self.offset, self.extent).contains(point)
+            && self.clip.is_none_or(|clip| clip.contains(point))
+    }
+}
+
+/// Widgets whose visible part is under `point`, topmost first: by `z`, and then by drawing
+/// order. Widgets outside of their parent, such as positioned ones, are found as well.
+pub(crate) fn hit_test(layout: &[FlattenedLayout], point: Vec2) -> Vec<usize> {
+    let mut hits: Vec<usize> = (0..layout.len())
+        .filter(|&i| layout[i].contains(point))
+        .collect();
+    hits.sort_by_key(|&i| std::cmp::Reverse((layout[i].z, i)));
+    hits
+}
+
+fn flatten_layout_inner(
+    base_offset: Vec2,
+    base_z: i32,
+    node: &LayoutTree,
+    result: &mut Vec<FlattenedLayout>,
+) {
+    let offset = base_offset + node.offset;
+    let z = base_z.saturating_add(node.z);
+
+    result.push(FlattenedLayout {
+        offset,
+        extent: node.extent,
+        z,
+        content_extent: node.content_extent,
+        clip: None,
+    });
+
+    for item in &node.children {
+        flatten_layout_inner(offset, z, item, result);
+    }
+}
+
+/// Layouts of every widget in window coordinates, in the same order as `flatten_widgets`
+pub(crate) fn flatten_layout(root: &LayoutTree) -> Vec<FlattenedLayout> {
+    let mut result = Vec::new();
+    flatten_layout_inner(Vec2::zero(), 0, root, &mut result);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::RecordingRenderer;
+
+    fn button(width: f32, height: f32) -> UiNode {
+        let mut node = UiNode::new(Widget::Button("x".to_owned()));
+        node.props.width = Some(Size::Px(width));
+        node.props.height = Some(Size::Px(height));
+        node
+    }
+
+    #[test]
+    fn hit_test_finds_topmost_first() {
+        let mut stack = UiNode::new(Widget::Stack);
+        let mut raised = button(50.0, 50.0);
+        raised.props.z_index = 1;
+        stack.push(raised);
+        stack.push(button(50.0, 50.0));
+        // Outside of the stack, which is only as large as the other buttons
+        let mut badge = button(10.0, 10.0);
+        badge.props.right = Some(-20.0);
+        badge.props.top = Some(0.0);
+        stack.push(badge);
+
+        let mut root = UiNode::new(Widget::Vertical);
+        root.push(stack);
+
+        let layout = calculate_ui_layout(&root, &mut RecordingRenderer::new(), vec2(200.0, 200.0));
+        let flat = flatten_layout(&layout);
+
+        assert_eq!(hit_test(&flat, vec2(25.0, 25.0)), [2, 3, 1, 0]);
+        assert_eq!(hit_test(&flat, vec2(65.0, 5.0)), [4, 0]);
+    }
+    /// A scrolling list with a named row per `(name, value)`, and a button under it. Values
+    /// of different lengths make rows measure differently.
+    fn panel(rows: &[(&str, &str)], list_width: Option<f32>) -> UiNode {
+        let mut list = UiNode::new(Widget::Scroll);
+        list.props.width = list_width.map(Size::Px);
+        list.props.gap = 2.0;
+        for &(name, value) in rows {
+            let mut row = UiNode::new(Widget::Horizontal);
+            row.string_uid = Some(name.to_owned());
+            row.push(Widget::Label(name.to_owned()));
+            row.push(Widget::Label(value.to_owned()));
+            list.push(row);
+        }
+
+        let mut root = UiNode::new(Widget::Vertical);
+        root.push(list);
+        root.push(button(40.0, 20.0));
+        root
+    }
+
+    #[test]
+    fn cached_layout_matches_layout_from_scratch() {
+        let mut text = RecordingRenderer::new();
+        let size = vec2(300.0, 200.0);
+        let mut tree = UiNode::new(Widget::Vertical);
+        let mut cache = LayoutCache::new();
+
+        let frames = vec![
+            panel(&[("a", "1"), ("b", "22"), ("c", "333")], None),
+            // Unchanged
+            panel(&[("a", "1"), ("b", "22"), ("c", "333")], None),
+            // Inserted
+            panel(
+                &[("a", "1"), ("d", "4444"), ("b", "22"), ("c", "333")],
+                None,
+            ),
+            // Removed
+            panel(&[("a", "1"), ("d", "4444"), ("c", "333")], None),
+            // Reordered, with new text
+            panel(&[("c", "333"), ("a", "10"), ("d", "4444")], None),
+            // A different property
+            panel(&[("c", "333"), ("a", "10"), ("d", "4444")], Some(120.0)),
+        ];
+
+        for (i, frame) in frames.into_iter().enumerate() {
+            tree.reconcile(frame);
+            let cached = cache.layout(&tree, &mut text, size);
+            let scratch = calculate_ui_layout(&tree, &mut text, size);
+
+            assert_eq!(
+                format!("{:?}", cached),
+                format!("{:?}", scratch),
+                "frame {}",
+                i
+            );
+            if i == 1 {
+                let stats = cache.stats();
+                assert_eq!((stats.visited, stats.measured, stats.arranged), (1, 0, 0));
+            }
+        }
+
+        // The changed row and its ancestors are laid out again, and their children checked
+        tree.reconcile(panel(
+            &[("c", "333"), ("a", "11"), ("d", "4444")],
+            Some(120.0),
+        ));
+        cache.layout(&tree, &mut text, size);
+        let stats = cache.stats();
+        assert_eq!((stats.visited, stats.measured), (8, 4));
+    }
+}
@@ -0,0 +1,542 @@
+use crate::ast::Unit;
+use std::str::{CharIndices, FromStr};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok<'input> {
+    Ident(&'input str),
+    Int(i32),
+    Float(f32),
+    Dimension(f32, Unit),
+    String(String),
+    /// `#` immediately followed by a word, such as `#my_button` or `#ff8800`
+    Hash(&'input str),
+    True,
+    False,
+    Include,
+    Template,
+    If,
+    Else,
+    For,
+    In,
+    /// `{path.to.value}`, a binding to the data model. Whitespace around the path is allowed.
+    Binding(&'input str),
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Colon,
+    Comma,
+    Semi,
+}
+
+impl<'input> std::fmt::Display for Tok<'input> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Ident(s) => write!(f, "{}", s),
+            Tok::Int(v) => write!(f, "{}", v),
+            Tok::Float(v) => write!(f, "{}", v),
+            Tok::Dimension(v, unit) => write!(f, "{}{}", v, unit),
+            Tok::String(s) => write!(f, "{:?}", s),
+            Tok::Hash(s) => write!(f, "#{}", s),
+            Tok::True => write!(f, "true"),
+            Tok::False => write!(f, "false"),
+            Tok::Include => write!(f, "include"),
+            Tok::Template => write!(f, "template"),
+            Tok::If => write!(f, "if"),
+            Tok::Else => write!(f, "else"),
+            Tok::For => write!(f, "for"),
+            Tok::In => write!(f, "in"),
+            Tok::Binding(s) => write!(f, "{{{}}}", s),
+            Tok::LBrace => write!(f, "{{"),
+            Tok::RBrace => write!(f, "}}"),
+            Tok::LParen => write!(f, "("),
+            Tok::RParen => write!(f, ")"),
+            Tok::Colon => write!(f, ":"),
+            Tok::Comma => write!(f, ","),
+            Tok::Semi => write!(f, ";"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidEscape(char),
+    InvalidUnicodeEscape,
+    InvalidRawString,
+    IntegerOverflow,
+    /// A float or dimension too large to be represented
+    NumberOverflow,
+    InvalidNumberSuffix(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub location: usize,
+    pub kind: LexErrorKind,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            LexErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexErrorKind::InvalidEscape(c) => write!(f, "unknown escape sequence `\\{}`", c),
+            LexErrorKind::InvalidUnicodeEscape => {
+                write!(f, "invalid unicode escape; expected `\\u{{XXXX}}`")
+            }
+            LexErrorKind::InvalidRawString => {
+                write!(f, "invalid raw string; expected `r\"...\"` or `r#\"...\"#`")
+            }
+            LexErrorKind::IntegerOverflow => write!(
+                f,
+                "integer literal out of range; must be between {} and {}",
+                i32::MIN,
+                i32::MAX
+            ),
+            LexErrorKind::NumberOverflow => write!(f, "number literal out of range"),
+            LexErrorKind::InvalidNumberSuffix(ref s) => {
+                write!(f, "invalid number suffix `{}`; expected `px` or `%`", s)
+            }
+        }
+    }
+}
+
+pub type Spanned<'input> = Result<(usize, Tok<'input>, usize), LexError>;
+
+/// Byte range of a `//` or `/* */` comment. Line comments do not include the newline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comment {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Hand-written lexer for .grui files. Skips whitespace as well as `//` and `/* */` comments,
+/// while keeping byte offsets into the original source.
+///
+/// Skipped comments are recorded, so that the formatter can put them back.
+pub struct Lexer<'input> {
+    input: &'input str,
+    chars: std::iter::Peekable<CharIndices<'input>>,
+    comments: Vec<Comment>,
+}
+
+impl<'input> Lexer<'input> {
+    pub fn new(input: &'input str) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().peekable(),
+            comments: Vec::new(),
+        }
+    }
+
+    /// Comments skipped so far, in source order
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn current_offset(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map(|&(i, _)| i)
+            .unwrap_or_else(|| self.input.len())
+    }
+
+    fn take_while(&mut self, start: usize, f: impl Fn(char) -> bool) -> (usize, &'input str) {
+        while let Some(c) = self.peek_char() {
+            if !f(c) {
+                break;
+            }
+            self.chars.next();
+        }
+
+        let end = self.current_offset();
+        (end, &self.input[start..end])
+    }
+
+    fn skip_line_comment(&mut self, start: usize) {
+        let (end, _) = self.take_while(start, |c| c != '\n');
+        self.comments.push(Comment { start, end });
+    }
+
+    fn skip_block_comment(&mut self, start: usize) -> Result<(), LexError> {
+        let mut prev = '\0';
+        for (i, c) in &mut self.chars {
+            if prev == '*' && c == '/' {
+                self.comments.push(Comment { start, end: i + 1 });
+                return Ok(());
+            }
+            prev = c;
+        }
+
+        Err(LexError {
+            location: start,
+            kind: LexErrorKind::UnterminatedComment,
+        })
+    }
+
+    fn string(&mut self, start: usize) -> Spanned<'input> {
+        let mut value = String::new();
+
+        while let Some((i, c)) = self.chars.next() {
+            match c {
+                '"' => return Ok((start, Tok::String(value), i + 1)),
+                '\\' => match self.chars.next() {
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, '0')) => value.push('\0'),
+                    Some((_, 'u')) => value.push(self.unicode_escape(i)?),
+                    Some((_, c)) => {
+                        return Err(LexError {
+                            location: i,
+                            kind: LexErrorKind::InvalidEscape(c),
+                        })
+                    }
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+
+        Err(LexError {
+            location: start,
+            kind: LexErrorKind::UnterminatedString,
+        })
+    }
+
+    /// Parses the `{XXXX}` part of a `\\u{XXXX}` escape
+    fn unicode_escape(&mut self, start: usize) -> Result<char, LexError> {
+        let err = LexError {
+            location: start,
+            kind: LexErrorKind::InvalidUnicodeEscape,
+        };
+
+        if self.chars.next().map(|(_, c)| c) != Some('{') {
+            return Err(err);
+        }
+
+        let digits_start = self.current_offset();
+        let (digits_end, digits) = self.take_while(digits_start, |c| c.is_ascii_hexdigit());
+
+        if digits.is_empty() || digits_end - digits_start > 6 || self.peek_char() != Some('}') {
+            return Err(err);
+        }
+        self.chars.next();
+
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .ok_or(err)
+    }
+
+    /// Raw strings: `r"..."`, `r#"..."#`, `r##"..."##` etc. No escapes are processed,
+    /// and the string ends at the first quote followed by the same number of hashes.
+    fn raw_string(&mut self, start: usize) -> Spanned<'input> {
+        let mut hashes = 0;
+        while self.peek_char() == Some('#') {
+            self.chars.next();
+            hashes += 1;
+        }
+
+        if self.chars.next().map(|(_, c)| c) != Some('"') {
+            return Err(LexError {
+                location: start,
+                kind: LexErrorKind::InvalidRawString,
+            });
+        }
+
+        let content_start = self.current_offset();
+
+        while let Some((i, c)) = self.chars.next() {
+            if c != '"' {
+                continue;
+            }
+
+            let closing = &self.input[i + 1..];
+            if closing.len() >= hashes && closing.bytes().take(hashes).all(|b| b == b'#') {
+                for _ in 0..hashes {
+                    self.chars.next();
+                }
+
+                let value = self.input[content_start..i].to_owned();
+                return Ok((start, Tok::String(value), i + 1 + hashes));
+            }
+        }
+
+        Err(LexError {
+            location: start,
+            kind: LexErrorKind::UnterminatedString,
+        })
+    }
+
+    /// Looks ahead from just past a `{` for a dotted path closed by `}`. Anything else is left
+    /// alone, so that the brace starts a block of children instead.
+    fn binding(&mut self, start: usize) -> Option<Spanned<'input>> {
+        let mut lookahead = self.chars.clone();
+        let mut path: Option<(usize, usize)> = None;
+        let mut expect_segment = true;
+
+        while let Some((i, c)) = lookahead.next() {
+            match c {
+                '}' if !expect_segment => {
+                    let (path_start, path_end) = path?;
+                    self.chars = lookahead;
+                    let tok = Tok::Binding(&self.input[path_start..path_end]);
+                    return Some(Ok((start, tok, i + 1)));
+                }
+                c if c.is_whitespace() && (path.is_none() || !expect_segment) => continue,
+                '.' if !expect_segment => expect_segment = true,
+                c if expect_segment && is_ident_start(c) => {
+                    let mut end = i + c.len_utf8();
+                    while let Some(&(j, c)) = lookahead.peek() {
+                        if !is_ident_continue(c) {
+                            break;
+                        }
+                        lookahead.next();
+                        end = j + c.len_utf8();
+                    }
+
+                    let path_start = path.map_or(i, |(s, _)| s);
+                    path = Some((path_start, end));
+                    expect_segment = false;
+                }
+                _ => return None,
+            }
+        }
+
+        None
+    }
+
+    /// Integers, floats and dimensions such as `-12`, `0.5`, `240px` or `50%`
+    fn number(&mut self, start: usize) -> Spanned<'input> {
+        let (mut end, _) = self.take_while(start + 1, |c| c.is_ascii_digit());
+        let mut is_float = false;
+
+        let mut lookahead = self.chars.clone();
+        lookahead.next();
+        if self.peek_char() == Some('.')
+            && lookahead.peek().is_some_and(|&(_, c)| c.is_ascii_digit())
+        {
+            self.chars.next();
+            end = self.take_while(end + 1, |c| c.is_ascii_digit()).0;
+            is_float = true;
+        }
+
+        let text = &self.input[start..end];
+        let (suffix_end, suffix) = self.take_while(end, |c| is_ident_continue(c) || c == '%');
+
+        let unit = match suffix {
+            "" => None,
+            "px" => Some(Unit::Px),
+            "%" => Some(Unit::Percent),
+            _ => {
+                return Err(LexError {
+                    location: end,
+                    kind: LexErrorKind::InvalidNumberSuffix(suffix.to_owned()),
+                })
+            }
+        };
+
+        let float = || {
+            Some(f32::from_str(text).unwrap())
+                .filter(|v| v.is_finite())
+                .ok_or(LexError {
+                    location: start,
+                    kind: LexErrorKind::NumberOverflow,
+                })
+        };
+
+        let tok = match unit {
+            Some(unit) => Tok::Dimension(float()?, unit),
+            None if is_float => Tok::Float(float()?),
+            None => Tok::Int(i32::from_str(text).map_err(|_| LexError {
+                location: start,
+                kind: LexErrorKind::IntegerOverflow,
+            })?),
+        };
+
+        Ok((start, tok, suffix_end))
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+pub fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(is_ident_continue)
+}
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Spanned<'input>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((i, c)) = self.chars.next() {
+            let single = |tok| Some(Ok((i, tok, i + 1)));
+
+            return match c {
+                c if c.is_whitespace() => continue,
+                '/' if self.peek_char() == Some('/') => {
+                    self.skip_line_comment(i);
+                    continue;
+                }
+                '/' if self.peek_char() == Some('*') => {
+                    self.chars.next();
+                    match self.skip_block_comment(i) {
+                        Ok(()) => continue,
+                        Err(err) => Some(Err(err)),
+                    }
+                }
+                '{' => self.binding(i).or_else(|| single(Tok::LBrace)),
+                '}' => single(Tok::RBrace),
+                '(' => single(Tok::LParen),
+                ')' => single(Tok::RParen),
+                ':' => single(Tok::Colon),
+                ',' => single(Tok::Comma),
+                ';' => single(Tok::Semi),
+                '#' => {
+                    let (end, s) = self.take_while(i + 1, is_ident_continue);
+                    Some(Ok((i, Tok::Hash(s), end)))
+                }
+                '"' => Some(self.string(i)),
+                'r' if self.peek_char() == Some('"') || self.peek_char() == Some('#') => {
+                    Some(self.raw_string(i))
+                }
+                c if is_ident_start(c) => {
+                    let (end, s) = self.take_while(i, is_ident_continue);
+                    let tok = match s {
+                        "true" => Tok::True,
+                        "false" => Tok::False,
+                        "include" => Tok::Include,
+                        "template" => Tok::Template,
+                        "if" => Tok::If,
+                        "else" => Tok::Else,
+                        "for" => Tok::For,
+                        "in" => Tok::In,
+                        s => Tok::Ident(s),
+                    };
+                    Some(Ok((i, tok, end)))
+                }
+                c if c.is_ascii_digit() => Some(self.number(i)),
+                '-' if self.peek_char().is_some_and(|c| c.is_ascii_digit()) => Some(self.number(i)),
+                c => Some(Err(LexError {
+                    location: i,
+                    kind: LexErrorKind::UnexpectedCharacter(c),
+                })),
+            };
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<Tok<'_>> {
+        Lexer::new(input).map(|tok| tok.unwrap().1).collect()
+    }
+
+    fn error(input: &str) -> LexErrorKind {
+        Lexer::new(input).find_map(Result::err).unwrap().kind
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            tokens(r#""a\"b\\c\n\t\r\0\u{e9}""#),
+            [Tok::String("a\"b\\c\n\t\r\0\u{e9}".to_owned())]
+        );
+        assert_eq!(error(r#""\q""#), LexErrorKind::InvalidEscape('q'));
+        assert_eq!(error(r#""\u{zz}""#), LexErrorKind::InvalidUnicodeEscape);
+        assert_eq!(error(r#""open"#), LexErrorKind::UnterminatedString);
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(
+            tokens(
+                r##"r"a\n" r#"say "hi""# r"two
+lines""##
+            ),
+            [
+                Tok::String("a\\n".to_owned()),
+                Tok::String("say \"hi\"".to_owned()),
+                Tok::String("two\nlines".to_owned()),
+            ]
+        );
+        assert_eq!(error(r##"r#"open"##), LexErrorKind::UnterminatedString);
+        // Only `r` followed by a quote or a hash starts a raw string
+        assert_eq!(tokens("r rows"), [Tok::Ident("r"), Tok::Ident("rows")]);
+    }
+
+    #[test]
+    fn numbers_and_dimensions() {
+        assert_eq!(
+            tokens("12 -3 0.5 240px 50% 1.5px"),
+            [
+                Tok::Int(12),
+                Tok::Int(-3),
+                Tok::Float(0.5),
+                Tok::Dimension(240.0, Unit::Px),
+                Tok::Dimension(50.0, Unit::Percent),
+                Tok::Dimension(1.5, Unit::Px),
+            ]
+        );
+        assert_eq!(
+            error("12em"),
+            LexErrorKind::InvalidNumberSuffix("em".to_owned())
+        );
+        assert_eq!(error("3000000000"), LexErrorKind::IntegerOverflow);
+        let huge = format!("1{}px", "0".repeat(40));
+        assert_eq!(error(&huge), LexErrorKind::NumberOverflow);
+    }
+
+    #[test]
+    fn bindings_and_blocks() {
+        assert_eq!(
+            tokens("{a} { b.c } {a.b.c}"),
+            [
+                Tok::Binding("a"),
+                Tok::Binding("b.c"),
+                Tok::Binding("a.b.c")
+            ]
+        );
+        // Anything but a dotted path is a block of children
+        assert_eq!(
+            tokens("{ label; }"),
+            [Tok::LBrace, Tok::Ident("label"), Tok::Semi, Tok::RBrace]
+        );
+        assert_eq!(
+            tokens("{a b}"),
+            [Tok::LBrace, Tok::Ident("a"), Tok::Ident("b"), Tok::RBrace]
+        );
+        assert_eq!(tokens("{}"), [Tok::LBrace, Tok::RBrace]);
+        assert_eq!(
+            tokens("{a: 1}"),
+            [
+                Tok::LBrace,
+                Tok::Ident("a"),
+                Tok::Colon,
+                Tok::Int(1),
+                Tok::RBrace
+            ]
+        );
+    }
+}
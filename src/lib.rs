@@ -0,0 +1,36 @@
+#[macro_use]
+extern crate lalrpop_util;
+lalrpop_mod!(#[allow(clippy::all, unused_parens)] pub grammar); // synthesized by LALRPOP
+
+pub mod ast;
+pub mod binding;
+pub mod diagnostics;
+pub mod display_list;
+mod emit;
+pub mod format;
+mod frame;
+pub mod grid;
+pub mod headless;
+pub mod layout;
+pub mod lexer;
+pub mod loader;
+pub mod recording;
+pub mod registry;
+pub mod render;
+mod scroll;
+pub mod serialize;
+pub mod software;
+pub mod syntax;
+mod templates;
+mod ui;
+mod validate;
+
+pub use frame::{FrameSource, InputEvent, UiHost};
+pub use ui::{
+    ClickableBehavior, EventPropagation, GenericWidget, Ui, UiInteractionState, UiNode, UiResult,
+    Widget, WidgetBehavior, WidgetBehaviorApi, WidgetEvent, WidgetId, WidgetNotFoundError,
+    WidgetProps, WidgetResponse, WidgetUid,
+};
+
+pub const INIT_WINDOW_SIZE: (u32, u32) = (500, 300);
+pub const FONT_PATH: &str = "resources/Roboto-Regular.ttf";
@@ -0,0 +1,303 @@
+use crate::ast::{self, FileId};
+use crate::diagnostics::{Diagnostic, Source, SourceMap};
+use crate::lexer::Lexer;
+use crate::registry::WidgetRegistry;
+use crate::syntax;
+use crate::templates::expand_templates;
+use crate::validate::validate_items;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub fn parse_ui_source(source: &Source, file: FileId) -> Result<Vec<ast::Node>, Diagnostic> {
+    syntax::parse(file, Lexer::new(&source.text))
+        .map_err(|e| Diagnostic::from_parse_error(source, &e))
+}
+
+pub fn read_ui_source(path: &Path) -> Result<Source, Diagnostic> {
+    let name = path.display().to_string();
+    let mut contents = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| Diagnostic::whole_file(name.as_str(), e.to_string()))?;
+
+    let mut source = Source::new(name, contents);
+    source.path = Some(path.to_owned());
+    Ok(source)
+}
+
+/// A parsed UI description with all `include` directives and templates expanded.
+pub struct LoadedUi {
+    pub nodes: Vec<ast::Node>,
+    pub sources: SourceMap,
+}
+
+/// Outcome of a load attempt, successful or not.
+struct LoadResult {
+    ui: Option<LoadedUi>,
+    diagnostics: Vec<Diagnostic>,
+    /// Every file the load depended on, including ones which could not be read
+    dependencies: Vec<PathBuf>,
+}
+
+struct IncludeResolver {
+    sources: SourceMap,
+    diagnostics: Vec<Diagnostic>,
+    dependencies: Vec<PathBuf>,
+    /// Files currently being expanded, for cycle detection
+    stack: Vec<PathBuf>,
+}
+
+impl IncludeResolver {
+    fn new() -> Self {
+        Self {
+            sources: SourceMap::default(),
+            diagnostics: Vec::new(),
+            dependencies: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    fn add_dependency(&mut self, path: &Path) {
+        if !self.dependencies.iter().any(|p| p == path) {
+            self.dependencies.push(path.to_owned());
+        }
+    }
+
+    fn load_file(&mut self, path: &Path) -> Option<Vec<ast::Node>> {
+        self.add_dependency(path);
+
+        match read_ui_source(path) {
+            Ok(source) => self.load_source(source),
+            Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                None
+            }
+        }
+    }
+
+    fn load_source(&mut self, source: Source) -> Option<Vec<ast::Node>> {
+        // Includes are relative to the including file, or the working directory for in-memory sources
+        let dir = source
+            .path
+            .as_ref()
+            .and_then(|p| p.parent())
+            .map(Path::to_owned)
+            .unwrap_or_default();
+
+        let file = self.sources.add(source);
+
+        match parse_ui_source(self.sources.get(file), file) {
+            Ok(nodes) => Some(self.expand(nodes, &dir)),
+            Err(diagnostic) => {
+                self.diagnostics.push(diagnostic);
+                None
+            }
+        }
+    }
+
+    fn expand(&mut self, nodes: Vec<ast::Node>, dir: &Path) -> Vec<ast::Node> {
+        let mut result = Vec::with_capacity(nodes.len());
+
+        for node in nodes {
+            match node {
+                ast::Node::Item(mut item) => {
+                    item.children = item.children.map(|ch| self.expand(ch, dir));
+                    result.push(ast::Node::Item(item));
+                }
+                ast::Node::Include(include) => {
+                    if let Some(mut nodes) = self.include(&include, dir) {
+                        result.append(&mut nodes);
+                    }
+                }
+                ast::Node::Template(mut template) => {
+                    template.body = self.expand(template.body, dir);
+                    result.push(ast::Node::Template(template));
+                }
+                ast::Node::If(mut cond) => {
+                    cond.body = self.expand(cond.body, dir);
+                    cond.else_body = cond.else_body.map(|body| self.expand(body, dir));
+                    result.push(ast::Node::If(cond));
+                }
+                ast::Node::For(mut each) => {
+                    each.body = self.expand(each.body, dir);
+                    result.push(ast::Node::For(each));
+                }
+            }
+        }
+
+        result
+    }
+
+    fn include(&mut self, include: &ast::Include, dir: &Path) -> Option<Vec<ast::Node>> {
+        let path = dir.join(&include.path);
+
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(err) => {
+                self.add_dependency(&path);
+                self.diagnostics.push(self.sources.diagnostic(
+                    include.span,
+                    format!("cannot include `{}`: {}", include.path, err),
+                ));
+                return None;
+            }
+        };
+
+        if let Some(pos) = self.stack.iter().position(|p| *p == path) {
+            let cycle: Vec<String> = self.stack[pos..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| p.display().to_string())
+                .collect();
+
+            self.diagnostics.push(self.sources.diagnostic(
+                include.span,
+                format!("include cycle: {}", cycle.join(" -> ")),
+            ));
+            return None;
+        }
+
+        self.stack.push(path.clone());
+        let nodes = self.load_file(&path);
+        self.stack.pop();
+
+        nodes
+    }
+
+    fn finish(self, nodes: Option<Vec<ast::Node>>) -> LoadResult {
+        // Any error in any of the files fails the whole load
+        let ui = match nodes {
+            Some(nodes) if self.diagnostics.is_empty() => Some(LoadedUi {
+                nodes,
+                sources: self.sources,
+            }),
+            _ => None,
+        };
+
+        LoadResult {
+            ui,
+            diagnostics: self.diagnostics,
+            dependencies: self.dependencies,
+        }
+    }
+}
+
+/// Loads a .grui file, with includes resolved relative to the file's directory.
+fn load_from_file(path: &Path) -> LoadResult {
+    let mut resolver = IncludeResolver::new();
+
+    let root = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    resolver.stack.push(root);
+
+    let nodes = resolver.load_file(path);
+    resolver.finish(nodes)
+}
+
+/// Loads source text from memory, with includes resolved relative to the working directory.
+fn load_from_source(source: Source) -> LoadResult {
+    let mut resolver = IncludeResolver::new();
+    let nodes = resolver.load_source(source);
+    resolver.finish(nodes)
+}
+
+enum UiOrigin {
+    File(PathBuf),
+    Memory(Source),
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Keeps the last successfully parsed AST of a .grui file around, and re-parses it
+/// only when the file, or any of the files it includes, changes.
+pub struct UiLoader {
+    origin: UiOrigin,
+    /// Files the current AST or diagnostics came from, along with their modification times
+    watched: Option<Vec<(PathBuf, Option<SystemTime>)>>,
+    ui: Option<LoadedUi>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl UiLoader {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self::with_origin(UiOrigin::File(path.into()))
+    }
+
+    /// A loader for in-memory source text. Includes are resolved relative to the working
+    /// directory, and only the included files are watched for changes.
+    pub fn from_string(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self::with_origin(UiOrigin::Memory(Source::new(name, text)))
+    }
+
+    fn with_origin(origin: UiOrigin) -> Self {
+        Self {
+            origin,
+            watched: None,
+            ui: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Checks whether any of the files changed since the last call, and re-parses them if so.
+    /// Templates in the new AST are expanded, and the result is validated against the
+    /// widgets in `registry`.
+    ///
+    /// Returns `true` if either a new AST has been loaded, or the diagnostics changed.
+    /// On parse failure, the previous AST is retained. ASTs with template or validation
+    /// errors are still used, as the offending items are skipped.
+    pub fn poll(&mut self, registry: &WidgetRegistry) -> bool {
+        if let Some(ref watched) = self.watched {
+            let changed = watched
+                .iter()
+                .any(|(path, modified)| modification_time(path) != *modified);
+
+            if !changed {
+                return false;
+            }
+        }
+
+        let res = match self.origin {
+            UiOrigin::File(ref path) => load_from_file(path),
+            UiOrigin::Memory(ref source) => load_from_source(source.clone()),
+        };
+
+        self.watched = Some(
+            res.dependencies
+                .into_iter()
+                .map(|path| {
+                    let modified = modification_time(&path);
+                    (path, modified)
+                })
+                .collect(),
+        );
+
+        match res.ui {
+            Some(mut ui) => {
+                let (nodes, mut diagnostics) = expand_templates(ui.nodes, &ui.sources);
+                diagnostics.extend(validate_items(registry, &ui.sources, &nodes));
+
+                ui.nodes = nodes;
+                self.diagnostics = diagnostics;
+                self.ui = Some(ui);
+            }
+            None => self.diagnostics = res.diagnostics,
+        }
+
+        true
+    }
+
+    /// The most recent AST which loaded successfully, if any.
+    pub fn ast(&self) -> Option<&[ast::Node]> {
+        self.ui.as_ref().map(|ui| ui.nodes.as_slice())
+    }
+
+    /// Problems found during the most recent load attempt.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
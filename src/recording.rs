@@ -0,0 +1,111 @@
+use crate::ast::Color;
+use crate::display_list::{write_rect, Primitive};
+use crate::render::{Fill, Rect, Renderer, TextMeasure, TextMetrics};
+
+use glam::Vec2;
+
+use std::fmt;
+
+/// One call made on a `RecordingRenderer`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    Draw(Primitive),
+    SetClip(Option<Rect>),
+}
+
+/// One command per line, in a compact form meant for reading and diffing
+impl fmt::Display for DrawCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrawCommand::Draw(primitive) => write!(f, "{}", primitive),
+            DrawCommand::SetClip(Some(clip)) => {
+                write!(f, "clip ")?;
+                write_rect(f, clip.pos, clip.size)
+            }
+            DrawCommand::SetClip(None) => write!(f, "clip none"),
+        }
+    }
+}
+
+/// Records draw calls instead of drawing, so what the UI draws can be inspected and
+/// compared without a window or a font.
+///
+/// Text is measured as if every character was half as wide as the font size, with the
+/// font's ascent, descent and line height at fixed fractions of it. Layouts therefore
+/// don't depend on the font files available, but don't match real text either.
+#[derive(Debug, Default)]
+pub struct RecordingRenderer {
+    pub commands: Vec<DrawCommand>,
+    clip: Option<Rect>,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn draw(&mut self, primitive: Primitive) {
+        self.commands.push(DrawCommand::Draw(primitive));
+    }
+}
+
+impl TextMeasure for RecordingRenderer {
+    fn text_width(&mut self, text: &str, size: f32) -> f32 {
+        text.chars().count() as f32 * size * 0.5
+    }
+
+    fn text_metrics(&mut self, size: f32) -> TextMetrics {
+        TextMetrics {
+            ascent: size * 0.8,
+            descent: size * -0.2,
+            line_height: size * 1.2,
+        }
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    fn fill_rect(&mut self, pos: Vec2, size: Vec2, fill: Fill) {
+        self.draw(Primitive::Rect { pos, size, fill });
+    }
+
+    fn fill_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, fill: Fill) {
+        self.draw(Primitive::RoundedRect {
+            pos,
+            size,
+            radius,
+            fill,
+        });
+    }
+
+    fn stroke_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, color: Color) {
+        self.draw(Primitive::StrokeRoundedRect {
+            pos,
+            size,
+            radius,
+            color,
+        });
+    }
+
+    fn text(&mut self, pos: Vec2, text: &str, size: f32, color: Color) {
+        self.draw(Primitive::Text {
+            pos,
+            text: text.to_owned(),
+            size,
+            color,
+        });
+    }
+
+    fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        // Consecutive changes leave only the last one in effect
+        if let Some(DrawCommand::SetClip(_)) = self.commands.last() {
+            self.commands.pop();
+        }
+
+        self.clip = clip;
+        self.commands.push(DrawCommand::SetClip(clip));
+    }
+}
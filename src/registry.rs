@@ -0,0 +1,247 @@
+use crate::ast;
+use crate::grid::{check_tracks, parse_tracks, GridSpec};
+use crate::layout::{Align, Justify};
+use crate::{UiNode, Widget};
+
+use std::collections::HashMap;
+
+/// Creates a widget node from its .grui declaration. Returns `None` if the item
+/// cannot be turned into a widget, in which case it is skipped.
+pub type WidgetFactory = Box<dyn Fn(&ast::Item) -> Option<UiNode>>;
+
+/// Checks an item beyond the kinds of its values, returning errors along with where they are.
+pub type ItemCheck = fn(&ast::Item) -> Vec<(ast::Span, String)>;
+
+/// The kinds of values accepted in a given position of a .grui item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    /// Integers, floats and dimensions
+    Number,
+    Bool,
+    Color,
+    Ident,
+    /// One of the given identifiers
+    Keyword(&'static [&'static str]),
+}
+
+impl ValueKind {
+    /// Bindings match any kind, as their values are only known during emission.
+    pub fn matches(self, value: &ast::Value) -> bool {
+        match (self, value) {
+            (ValueKind::Keyword(words), ast::Value::Ident(s)) => words.contains(&s.as_str()),
+            _ => matches!(
+                (self, value),
+                (_, ast::Value::Binding(_))
+                    | (ValueKind::String, ast::Value::String(_))
+                    | (ValueKind::Number, ast::Value::Int(_))
+                    | (ValueKind::Number, ast::Value::Float(_))
+                    | (ValueKind::Number, ast::Value::Dimension(..))
+                    | (ValueKind::Bool, ast::Value::Bool(_))
+                    | (ValueKind::Color, ast::Value::Color(_))
+                    | (ValueKind::Ident, ast::Value::Ident(_))
+            ),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ValueKind::String => "string",
+            ValueKind::Number => "number",
+            ValueKind::Bool => "boolean",
+            ValueKind::Color => "color",
+            ValueKind::Ident => "identifier",
+            ValueKind::Keyword(_) => "keyword",
+        }
+    }
+}
+
+/// Properties understood by every widget
+pub const COMMON_PROPERTIES: &[(&str, ValueKind)] = &[
+    ("width", ValueKind::Number),
+    ("height", ValueKind::Number),
+    ("min_width", ValueKind::Number),
+    ("max_width", ValueKind::Number),
+    ("min_height", ValueKind::Number),
+    ("max_height", ValueKind::Number),
+    ("padding", ValueKind::Number),
+    ("grow", ValueKind::Number),
+    ("shrink", ValueKind::Number),
+    ("row", ValueKind::Number),
+    ("column", ValueKind::Number),
+    ("row_span", ValueKind::Number),
+    ("column_span", ValueKind::Number),
+    ("justify_self", ValueKind::Keyword(Align::KEYWORDS)),
+    ("align_self", ValueKind::Keyword(Align::KEYWORDS)),
+    ("left", ValueKind::Number),
+    ("top", ValueKind::Number),
+    ("right", ValueKind::Number),
+    ("bottom", ValueKind::Number),
+    ("z_index", ValueKind::Number),
+    ("enabled", ValueKind::Bool),
+    ("tooltip", ValueKind::String),
+];
+
+/// Properties of the built-in containers, `horizontal`, `vertical` and `scroll`
+pub const BOX_PROPERTIES: &[(&str, ValueKind)] = &[
+    ("gap", ValueKind::Number),
+    ("justify", ValueKind::Keyword(Justify::KEYWORDS)),
+    ("align", ValueKind::Keyword(Align::KEYWORDS)),
+];
+
+/// Properties of `grid` containers. Tracks are lists such as `"auto 1fr 120px"`.
+pub const GRID_PROPERTIES: &[(&str, ValueKind)] = &[
+    ("columns", ValueKind::String),
+    ("rows", ValueKind::String),
+    ("gap", ValueKind::Number),
+];
+
+/// Properties of `label` and `button`, whose `color` is that of the text and the background
+pub const TEXT_PROPERTIES: &[(&str, ValueKind)] = &[("color", ValueKind::Color)];
+
+/// Properties of `stack` containers, which `align` their children on both axes
+pub const STACK_PROPERTIES: &[(&str, ValueKind)] =
+    &[("align", ValueKind::Keyword(Align::KEYWORDS))];
+
+/// The shape of .grui items a widget kind accepts, used to validate files before emission.
+#[derive(Debug, Clone, Default)]
+pub struct WidgetSignature {
+    /// Kind of the positional value, or `None` if the widget does not take one
+    pub value: Option<ValueKind>,
+    /// Whether the widget accepts a `{ ... }` block of children
+    pub children: bool,
+    /// Properties accepted on top of `COMMON_PROPERTIES`
+    pub properties: Vec<(&'static str, ValueKind)>,
+    pub check: Option<ItemCheck>,
+}
+
+impl WidgetSignature {
+    pub fn leaf(value: ValueKind) -> Self {
+        Self {
+            value: Some(value),
+            ..Default::default()
+        }
+    }
+
+    pub fn container() -> Self {
+        Self {
+            children: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn property_kind(&self, name: &str) -> Option<ValueKind> {
+        COMMON_PROPERTIES
+            .iter()
+            .chain(&self.properties)
+            .find(|(n, _)| *n == name)
+            .map(|(_, kind)| *kind)
+    }
+}
+
+struct WidgetKind {
+    signature: WidgetSignature,
+    factory: WidgetFactory,
+}
+
+/// Maps .grui identifiers such as `button` to the widgets they create.
+pub struct WidgetRegistry {
+    kinds: HashMap<String, WidgetKind>,
+}
+
+impl WidgetRegistry {
+    /// A registry without any widgets. See `Default` for one with the built-in widgets.
+    pub fn empty() -> Self {
+        Self {
+            kinds: HashMap::new(),
+        }
+    }
+
+    /// Registers a widget kind, replacing any previous one with the same identifier.
+    pub fn register<F>(&mut self, ident: &str, signature: WidgetSignature, factory: F)
+    where
+        F: Fn(&ast::Item) -> Option<UiNode> + 'static,
+    {
+        self.kinds.insert(
+            ident.to_owned(),
+            WidgetKind {
+                signature,
+                factory: Box::new(factory),
+            },
+        );
+    }
+
+    pub fn get(&self, ident: &str) -> Option<&WidgetFactory> {
+        self.kinds.get(ident).map(|k| &k.factory)
+    }
+
+    pub fn signature(&self, ident: &str) -> Option<&WidgetSignature> {
+        self.kinds.get(ident).map(|k| &k.signature)
+    }
+}
+
+impl Default for WidgetRegistry {
+    fn default() -> Self {
+        let mut res = Self::empty();
+
+        let text_signature = WidgetSignature {
+            properties: TEXT_PROPERTIES.to_vec(),
+            ..WidgetSignature::leaf(ValueKind::String)
+        };
+
+        res.register("label", text_signature.clone(), |item| {
+            item.value()
+                .and_then(ast::Value::as_str)
+                .map(|value| UiNode::new(Widget::Label(value.to_owned())))
+        });
+
+        res.register("button", text_signature, |item| {
+            item.value()
+                .and_then(ast::Value::as_str)
+                .map(|value| UiNode::new(Widget::Button(value.to_owned())))
+        });
+
+        let box_signature = WidgetSignature {
+            properties: BOX_PROPERTIES.to_vec(),
+            ..WidgetSignature::container()
+        };
+
+        res.register("horizontal", box_signature.clone(), |_| {
+            Some(UiNode::new(Widget::Horizontal))
+        });
+        res.register("vertical", box_signature.clone(), |_| {
+            Some(UiNode::new(Widget::Vertical))
+        });
+        res.register("scroll", box_signature, |_| {
+            Some(UiNode::new(Widget::Scroll))
+        });
+
+        let stack_signature = WidgetSignature {
+            properties: STACK_PROPERTIES.to_vec(),
+            ..WidgetSignature::container()
+        };
+        res.register("stack", stack_signature, |_| {
+            Some(UiNode::new(Widget::Stack))
+        });
+
+        let grid_signature = WidgetSignature {
+            properties: GRID_PROPERTIES.to_vec(),
+            check: Some(check_tracks),
+            ..WidgetSignature::container()
+        };
+
+        res.register("grid", grid_signature, |item| {
+            let tracks = |name| match item.properties.get(name).and_then(ast::Value::as_str) {
+                Some(tracks) => parse_tracks(tracks).ok(),
+                None => Some(Vec::new()),
+            };
+
+            Some(UiNode::new(Widget::Grid(GridSpec {
+                columns: tracks("columns")?,
+                rows: tracks("rows")?,
+            })))
+        });
+
+        res
+    }
+}
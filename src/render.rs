@@ -0,0 +1,227 @@
+use crate::ast::Color;
+
+use glam::Vec2;
+use nanovg::{Alignment, Clip, Font, Frame, Gradient, PathOptions, Scissor, TextOptions};
+
+/// How the inside of a shape is painted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+    Solid(Color),
+    /// Linear gradient between two points, clamped to the end colors beyond them
+    LinearGradient {
+        start: Vec2,
+        end: Vec2,
+        start_color: Color,
+        end_color: Color,
+    },
+}
+
+/// An axis-aligned rectangle in window coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub pos: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub fn new(pos: Vec2, size: Vec2) -> Self {
+        Self { pos, size }
+    }
+
+    pub fn max(&self) -> Vec2 {
+        self.pos + self.size
+    }
+
+    /// The area covered by both rectangles, which is empty if they do not overlap
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let pos = self.pos.max(other.pos);
+        let max = self.max().min(other.max()).max(pos);
+        Rect::new(pos, max - pos)
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.pos).all() && point.cmplt(self.max()).all()
+    }
+}
+
+/// Vertical measurements of a font at a given size. `descent` is negative, as it extends
+/// below the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    /// Distance between the baselines of consecutive lines
+    pub line_height: f32,
+}
+
+/// Measures text for layout, without having to draw it.
+pub trait TextMeasure {
+    /// Horizontal advance of `text` drawn at the given font size
+    fn text_width(&mut self, text: &str, size: f32) -> f32;
+    fn text_metrics(&mut self, size: f32) -> TextMetrics;
+}
+
+/// The drawing operations widgets are drawn with. Text is left-aligned, and vertically
+/// centered on the given position.
+///
+/// All drawing is limited to the current clip rectangle, if there is one.
+pub trait Renderer: TextMeasure {
+    fn fill_rect(&mut self, pos: Vec2, size: Vec2, fill: Fill);
+    fn fill_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, fill: Fill);
+    fn stroke_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, color: Color);
+    fn text(&mut self, pos: Vec2, text: &str, size: f32, color: Color);
+
+    fn clip(&self) -> Option<Rect>;
+    /// Replaces the clip rectangle; `None` allows drawing anywhere
+    fn set_clip(&mut self, clip: Option<Rect>);
+}
+
+/// Runs `draw` with the clip narrowed down to `rect`, then restores the previous clip.
+pub fn with_clip(r: &mut dyn Renderer, rect: Rect, draw: impl FnOnce(&mut dyn Renderer)) {
+    let prev = r.clip();
+    r.set_clip(Some(prev.map_or(rect, |clip| clip.intersect(&rect))));
+    draw(r);
+    r.set_clip(prev);
+}
+
+fn nanovg_color(c: Color) -> nanovg::Color {
+    nanovg::Color::from_rgba(c.r, c.g, c.b, c.a)
+}
+
+fn nanovg_fill(path: &nanovg::Path, fill: Fill) {
+    match fill {
+        Fill::Solid(color) => path.fill(nanovg_color(color), Default::default()),
+        Fill::LinearGradient {
+            start,
+            end,
+            start_color,
+            end_color,
+        } => path.fill(
+            Gradient::Linear {
+                start: (start.x(), start.y()),
+                end: (end.x(), end.y()),
+                start_color: nanovg_color(start_color),
+                end_color: nanovg_color(end_color),
+            },
+            Default::default(),
+        ),
+    }
+}
+
+/// Draws into a NanoVG frame, using a single font for all text.
+pub struct NanovgRenderer<'a, 'f> {
+    frame: &'a Frame<'f>,
+    font: Font<'f>,
+    clip: Option<Rect>,
+}
+
+impl<'a, 'f> NanovgRenderer<'a, 'f> {
+    pub fn new(frame: &'a Frame<'f>, font: Font<'f>) -> Self {
+        Self {
+            frame,
+            font,
+            clip: None,
+        }
+    }
+
+    fn nanovg_clip(&self) -> Clip {
+        match self.clip {
+            Some(rect) => Clip::Scissor(Scissor {
+                x: rect.pos.x(),
+                y: rect.pos.y(),
+                width: rect.size.x(),
+                height: rect.size.y(),
+                transform: None,
+            }),
+            None => Clip::None,
+        }
+    }
+
+    fn path_options(&self) -> PathOptions {
+        PathOptions {
+            clip: self.nanovg_clip(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a, 'f> TextMeasure for NanovgRenderer<'a, 'f> {
+    fn text_width(&mut self, text: &str, size: f32) -> f32 {
+        let options = TextOptions {
+            size,
+            ..Default::default()
+        };
+        self.frame
+            .text_bounds(self.font, (0.0, 0.0), text, options)
+            .0
+    }
+
+    fn text_metrics(&mut self, size: f32) -> TextMetrics {
+        let options = TextOptions {
+            size,
+            ..Default::default()
+        };
+        let metrics = self.frame.text_metrics(self.font, options);
+
+        TextMetrics {
+            ascent: metrics.ascender,
+            descent: metrics.descender,
+            line_height: metrics.line_height,
+        }
+    }
+}
+
+impl<'a, 'f> Renderer for NanovgRenderer<'a, 'f> {
+    fn fill_rect(&mut self, pos: Vec2, size: Vec2, fill: Fill) {
+        self.frame.path(
+            |path| {
+                path.rect((pos.x(), pos.y()), (size.x(), size.y()));
+                nanovg_fill(&path, fill);
+            },
+            self.path_options(),
+        );
+    }
+
+    fn fill_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, fill: Fill) {
+        self.frame.path(
+            |path| {
+                path.rounded_rect((pos.x(), pos.y()), (size.x(), size.y()), radius);
+                nanovg_fill(&path, fill);
+            },
+            self.path_options(),
+        );
+    }
+
+    fn stroke_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, color: Color) {
+        self.frame.path(
+            |path| {
+                path.rounded_rect((pos.x(), pos.y()), (size.x(), size.y()), radius);
+                path.stroke(nanovg_color(color), Default::default());
+            },
+            self.path_options(),
+        );
+    }
+
+    fn text(&mut self, pos: Vec2, text: &str, size: f32, color: Color) {
+        self.frame.text(
+            self.font,
+            (pos.x(), pos.y()),
+            text,
+            TextOptions {
+                size,
+                color: nanovg_color(color),
+                align: Alignment::new().left().middle(),
+                clip: self.nanovg_clip(),
+                ..Default::default()
+            },
+        );
+    }
+
+    fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+}
@@ -0,0 +1,216 @@
+use crate::layout::{axis, from_axes, FlattenedLayout, FlattenedWidgetNode};
+use crate::render::Rect;
+use crate::ui::{UiInteractionState, WidgetUid};
+
+use glam::Vec2;
+
+use std::collections::HashMap;
+
+/// Thickness of scrollbars, which are drawn over the right and bottom edges of the content
+pub(crate) const SCROLLBAR_WIDTH: f32 = 8.0;
+const MIN_THUMB_LENGTH: f32 = 20.0;
+
+/// A scrollbar thumb being dragged with the mouse
+#[derive(Debug, Clone)]
+pub(crate) struct ScrollDrag {
+    pub(crate) widget: WidgetUid,
+    axis: usize,
+    /// Distance from the start of the thumb to where it was grabbed
+    grab: f32,
+}
+
+impl ScrollDrag {
+    pub(crate) fn is_dragging(&self, widget: &WidgetUid, axis: usize) -> bool {
+        self.widget == *widget && self.axis == axis
+    }
+}
+
+/// The scrollbar of a scroll container along one axis
+pub(crate) struct Scrollbar {
+    pub(crate) axis: usize,
+    pub(crate) track: Rect,
+    pub(crate) thumb: Rect,
+}
+
+impl Scrollbar {
+    /// The scroll offset which puts the start of the thumb at `thumb_start`, along the axis
+    /// of the scrollbar
+    fn offset_at(&self, thumb_start: f32, max_offset: f32) -> f32 {
+        let a = self.axis;
+        let free = axis(self.track.size, a) - axis(self.thumb.size, a);
+        if free <= 0.0 {
+            return 0.0;
+        }
+
+        let fraction = (thumb_start - axis(self.track.pos, a)) / free;
+        fraction.clamp(0.0, 1.0) * max_offset
+    }
+}
+
+/// How far the content of a scroll container can move, or zero for other widgets
+fn max_offset(layout: &FlattenedLayout) -> Vec2 {
+    layout.content_extent.map_or(Vec2::zero(), |content| {
+        (content - layout.extent).max(Vec2::zero())
+    })
+}
+
+fn clamp_offset(layout: &FlattenedLayout, offset: Vec2) -> Vec2 {
+    offset.max(Vec2::zero()).min(max_offset(layout))
+}
+
+/// Moves the children of scroll containers by the containers' scroll offsets, and clips them
+/// to the containers. Offsets are clamped to the content first, so that content which
+/// shrank is not scrolled past its end.
+pub(crate) fn apply_scroll(
+    widgets: &[FlattenedWidgetNode],
+    layout: &mut [FlattenedLayout],
+    offsets: &mut HashMap<WidgetUid, Vec2>,
+) {
+    for (i, widget) in widgets.iter().enumerate() {
+        if layout[i].content_extent.is_none() {
+            continue;
+        }
+
+        let offset = match offsets.get_mut(&widget.uid) {
+            Some(offset) => {
+                *offset = clamp_offset(&layout[i], *offset);
+                *offset
+            }
+            None => Vec2::zero(),
+        };
+
+        // Outer containers come first, so the viewport has already been moved and clipped
+        let viewport = Rect::new(layout[i].offset, layout[i].extent);
+        let clip = layout[i]
+            .clip
+            .map_or(viewport, |clip| clip.intersect(&viewport));
+
+        for child in &mut layout[i + 1..=i + widget.subtree_size] {
+            child.offset -= offset;
+            child.clip = Some(child.clip.map_or(clip, |c| c.intersect(&clip)));
+        }
+    }
+}
+
+/// Scrollbars of a scroll container, along the axes its content overflows in
+pub(crate) fn scrollbars(layout: &FlattenedLayout, offset: Vec2) -> Vec<Scrollbar> {
+    let max = max_offset(layout);
+    let content = match layout.content_extent {
+        Some(content) => content,
+        None => return Vec::new(),
+    };
+
+    (0..2)
+        .filter(|&a| axis(max, a) > 0.0)
+        .map(|a| {
+            let cross = 1 - a;
+            let track_length = axis(layout.extent, a);
+            let track = Rect::new(
+                layout.offset + from_axes(0.0, axis(layout.extent, cross) - SCROLLBAR_WIDTH, a),
+                from_axes(track_length, SCROLLBAR_WIDTH, a),
+            );
+
+            let thumb_length = (track_length * axis(layout.extent, a) / axis(content, a))
+                .max(MIN_THUMB_LENGTH)
+                .min(track_length);
+            let thumb_start = (track_length - thumb_length) * axis(offset, a) / axis(max, a);
+            let thumb = Rect::new(
+                track.pos + from_axes(thumb_start, 0.0, a),
+                from_axes(thumb_length, SCROLLBAR_WIDTH, a),
+            );
+
+            Scrollbar {
+                axis: a,
+                track,
+                thumb,
+            }
+        })
+        .collect()
+}
+
+/// Scrolls containers with the wheel and with their scrollbars. The wheel scrolls the
+/// innermost container under the mouse which can move in that direction. Pressing on a
+/// scrollbar starts dragging its thumb, and pressing on its track jumps there first.
+///
+/// `hovered` are the widgets under the mouse, topmost first. Returns whether the mouse is
+/// used by a scrollbar this frame, in which case widgets should not react to it.
+pub(crate) fn handle_scroll_input(
+    state: &mut UiInteractionState,
+    widgets: &[FlattenedWidgetNode],
+    layout: &[FlattenedLayout],
+    hovered: &[usize],
+    mouse: Vec2,
+    wheel: Vec2,
+) -> bool {
+    let was_dragging = state.scroll_drag.is_some();
+    if !state.mouse_down {
+        state.scroll_drag = None;
+    }
+
+    if wheel != Vec2::zero() {
+        let target = hovered.iter().find(|&&i| {
+            let max = max_offset(&layout[i]);
+            (0..2).any(|a| axis(max, a) > 0.0 && axis(wheel, a) != 0.0)
+        });
+
+        if let Some(&i) = target {
+            let offset = state
+                .scroll_offsets
+                .entry(widgets[i].uid.clone())
+                .or_insert_with(Vec2::zero);
+            *offset = clamp_offset(&layout[i], *offset - wheel);
+        }
+    }
+
+    if state.mouse_pressed {
+        let grabbed = hovered.iter().find_map(|&i| {
+            let offset = state.scroll_offset(&widgets[i].uid);
+            scrollbars(&layout[i], offset)
+                .into_iter()
+                .find(|bar| bar.track.contains(mouse))
+                .map(|bar| {
+                    let a = bar.axis;
+                    let grab = if bar.thumb.contains(mouse) {
+                        axis(mouse - bar.thumb.pos, a)
+                    } else {
+                        axis(bar.thumb.size, a) * 0.5
+                    };
+
+                    ScrollDrag {
+                        widget: widgets[i].uid.clone(),
+                        axis: a,
+                        grab,
+                    }
+                })
+        });
+
+        if grabbed.is_some() {
+            state.scroll_drag = grabbed;
+        }
+    }
+
+    if let Some(drag) = state.scroll_drag.clone() {
+        let i = widgets.iter().position(|w| w.uid == drag.widget);
+        let offset = state.scroll_offset(&drag.widget);
+        let bar = i.and_then(|i| {
+            scrollbars(&layout[i], offset)
+                .into_iter()
+                .find(|bar| bar.axis == drag.axis)
+                .map(|bar| (i, bar))
+        });
+
+        match bar {
+            Some((i, bar)) => {
+                let a = drag.axis;
+                let moved =
+                    bar.offset_at(axis(mouse, a) - drag.grab, axis(max_offset(&layout[i]), a));
+                let offset = from_axes(moved, axis(offset, 1 - a), a);
+                state.scroll_offsets.insert(drag.widget, offset);
+            }
+            // The container is gone, or doesn't overflow anymore
+            None => state.scroll_drag = None,
+        }
+    }
+
+    was_dragging || state.scroll_drag.is_some()
+}
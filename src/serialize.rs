@@ -0,0 +1,251 @@
+use crate::ast;
+use crate::format::format_nodes;
+use crate::grid::format_tracks;
+use crate::layout::Size;
+use crate::{UiNode, Widget, WidgetProps};
+
+/// Writes the children of `root` out as .grui source, such as for snapshotting a UI
+/// which code has added widgets to. The root itself is the implicit top-level container.
+pub fn serialize_ui(root: &UiNode) -> String {
+    let nodes: Vec<ast::Node> = root
+        .children
+        .iter()
+        .map(|(_, child)| ast::Node::Item(node_to_item(child)))
+        .collect();
+
+    format_nodes(&nodes)
+}
+
+/// Converts a live widget node back into the .grui item which would create it.
+pub fn node_to_item(node: &UiNode) -> ast::Item {
+    let mut values = Vec::new();
+    let mut children: Vec<&UiNode> = node.children.iter().map(|(_, ch)| ch).collect();
+
+    let (ident, container) = match node.widget {
+        Widget::Label(ref text) => {
+            values.push(ast::Value::String(text.clone()));
+            ("label", false)
+        }
+        Widget::Button(ref text) => {
+            values.push(ast::Value::String(text.clone()));
+            ("button", false)
+        }
+        Widget::Horizontal => ("horizontal", true),
+        Widget::Vertical => ("vertical", true),
+        Widget::Grid(_) => ("grid", true),
+        Widget::Scroll => ("scroll", true),
+        Widget::Stack => ("stack", true),
+        Widget::Generic(ref generic) => {
+            // `Ui::button` creates a generic widget holding a single label
+            if let [child] = children.as_slice() {
+                if let Widget::Label(ref text) = child.widget {
+                    if child.children.is_empty() {
+                        values.push(ast::Value::String(text.clone()));
+                        children.clear();
+                    }
+                }
+            }
+            (generic.draw_style, false)
+        }
+    };
+
+    let children = if container || !children.is_empty() {
+        Some(
+            children
+                .into_iter()
+                .map(|ch| ast::Node::Item(node_to_item(ch)))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    ast::Item {
+        uid: node.string_uid.clone(),
+        ident: ident.to_owned(),
+        values,
+        properties: props_to_ast(node),
+        children,
+        span: ast::Span::default(),
+    }
+}
+
+fn props_to_ast(node: &UiNode) -> ast::Properties {
+    let props = &node.props;
+    let mut res = Vec::new();
+    let mut push = |name: &str, value: ast::Value| {
+        res.push(ast::Property {
+            name: name.to_owned(),
+            value,
+            span: ast::Span::default(),
+        })
+    };
+
+    if let Widget::Grid(ref spec) = node.widget {
+        push("columns", ast::Value::String(format_tracks(&spec.columns)));
+        if !spec.rows.is_empty() {
+            push("rows", ast::Value::String(format_tracks(&spec.rows)));
+        }
+    }
+
+    let px = |v| ast::Value::Dimension(v, ast::Unit::Px);
+    let size = |size| match size {
+        Size::Px(v) => px(v),
+        Size::Percent(v) => ast::Value::Dimension(v, ast::Unit::Percent),
+    };
+
+    if let Some(width) = props.width {
+        push("width", size(width));
+    }
+    if let Some(height) = props.height {
+        push("height", size(height));
+    }
+
+    let sizes = [
+        ("min_width", props.min_width),
+        ("max_width", props.max_width),
+        ("min_height", props.min_height),
+        ("max_height", props.max_height),
+        ("padding", props.padding),
+    ];
+    for (name, value) in sizes {
+        if let Some(value) = value {
+            push(name, px(value));
+        }
+    }
+
+    let offsets = [
+        ("left", props.left),
+        ("top", props.top),
+        ("right", props.right),
+        ("bottom", props.bottom),
+    ];
+    for (name, value) in offsets {
+        if let Some(value) = value {
+            push(name, px(value));
+        }
+    }
+
+    let default = WidgetProps::default();
+    let weights = [
+        ("grow", props.grow, default.grow),
+        ("shrink", props.shrink, default.shrink),
+    ];
+    for (name, value, default) in weights {
+        if value != default {
+            push(name, ast::Value::Float(value));
+        }
+    }
+    if props.gap != default.gap {
+        push("gap", px(props.gap));
+    }
+    if props.justify != default.justify {
+        push(
+            "justify",
+            ast::Value::Ident(props.justify.keyword().to_owned()),
+        );
+    }
+    if props.align != default.align {
+        push("align", ast::Value::Ident(props.align.keyword().to_owned()));
+    }
+
+    let cells = [("row", props.row), ("column", props.column)];
+    for (name, value) in cells {
+        if let Some(value) = value {
+            push(name, ast::Value::Int(value as i32));
+        }
+    }
+    let spans = [
+        ("row_span", props.row_span, default.row_span),
+        ("column_span", props.column_span, default.column_span),
+    ];
+    for (name, value, default) in spans {
+        if value != default {
+            push(name, ast::Value::Int(value as i32));
+        }
+    }
+    let self_aligns = [
+        ("justify_self", props.justify_self),
+        ("align_self", props.align_self),
+    ];
+    for (name, value) in self_aligns {
+        if let Some(align) = value {
+            push(name, ast::Value::Ident(align.keyword().to_owned()));
+        }
+    }
+    if props.z_index != default.z_index {
+        push("z_index", ast::Value::Int(props.z_index));
+    }
+    if !props.enabled {
+        push("enabled", ast::Value::Bool(false));
+    }
+    if let Some(ref tooltip) = props.tooltip {
+        push("tooltip", ast::Value::String(tooltip.clone()));
+    }
+    if let Some(color) = props.color {
+        push("color", ast::Value::Color(color));
+    }
+
+    ast::Properties(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{Source, SourceMap};
+    use crate::emit::{emit_gui_items, EmitContext};
+    use crate::loader::parse_ui_source;
+    use crate::registry::WidgetRegistry;
+    use crate::ui::{Ui, UiContext, UiInteractionState, WidgetUid};
+    use crate::validate::validate_items;
+
+    /// Parses and validates `text`, and emits it with `code` run afterwards, as in a frame
+    fn emit(text: &str, code: impl FnOnce(&mut Ui)) -> UiNode {
+        let registry = WidgetRegistry::default();
+        let mut sources = SourceMap::default();
+        let file = sources.add(Source::new("test.grui", text));
+        let nodes = parse_ui_source(sources.get(file), file).unwrap();
+        let diagnostics = validate_items(&registry, &sources, &nodes);
+        assert!(diagnostics.is_empty(), "{:?}\n{}", diagnostics, text);
+
+        let mut root = UiNode::new(Widget::Vertical);
+        let state = UiInteractionState::default();
+        let mut ui = Ui::new(
+            &mut root,
+            UiContext {
+                uid: WidgetUid(Vec::new()),
+                interaction_state: &state,
+            },
+        );
+        let mut emit = EmitContext {
+            registry: &registry,
+            model: &(),
+        };
+        emit_gui_items(&mut ui, &mut emit, &nodes);
+        code(&mut ui);
+        root
+    }
+
+    #[test]
+    fn serialized_ui_emits_the_same_ui() {
+        let text = r#"
+            #title label "Settings" (color: #ffcc00);
+            grid (columns: "auto 1fr 120px", gap: 4) {
+                label "Name" (row: 1, column: 1);
+                button "Rename" (row: 1, column: 2, column_span: 2, enabled: false);
+            }
+            scroll (height: 50%, padding: 6) {
+                horizontal (justify: space_between) { label "a\nb"; }
+            }
+        "#;
+
+        let root = emit(text, |ui| {
+            ui.button("Added by code");
+        });
+        let serialized = serialize_ui(&root);
+        assert!(serialized.contains("button \"Added by code\";"));
+
+        let reemitted = emit(&serialized, |_| ());
+        assert_eq!(serialize_ui(&reemitted), serialized);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::ast::Color;
+use crate::render::{Fill, Rect, Renderer, TextMeasure, TextMetrics};
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use glam::{vec2, Vec2};
+use tiny_skia::{
+    FillRule, GradientStop, LinearGradient, Mask, Paint, Path, PathBuilder, Pixmap, Point,
+    PremultipliedColorU8, Shader, SpreadMode, Stroke, Transform,
+};
+
+/// Renders into an in-memory image on the CPU, for use without a window or a GPU.
+pub struct SoftwareRenderer {
+    pixmap: Pixmap,
+    font: FontVec,
+    /// The clip rectangle, and a mask covering it for tiny-skia
+    clip: Option<(Rect, Mask)>,
+}
+
+impl SoftwareRenderer {
+    /// Returns `None` if either dimension is zero.
+    pub fn new(width: u32, height: u32, font: FontVec) -> Option<Self> {
+        Some(Self {
+            pixmap: Pixmap::new(width, height)?,
+            font,
+            clip: None,
+        })
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        self.pixmap.fill(skia_color(color));
+    }
+
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+
+    fn fill_path(&mut self, path: &Path, fill: Fill) {
+        let shader = match fill {
+            Fill::Solid(color) => Shader::SolidColor(skia_color(color)),
+            Fill::LinearGradient {
+                start,
+                end,
+                start_color,
+                end_color,
+            } => LinearGradient::new(
+                Point::from_xy(start.x(), start.y()),
+                Point::from_xy(end.x(), end.y()),
+                vec![
+                    GradientStop::new(0.0, skia_color(start_color)),
+                    GradientStop::new(1.0, skia_color(end_color)),
+                ],
+                SpreadMode::Pad,
+                Transform::identity(),
+            )
+            // Degenerate gradients paint the start color
+            .unwrap_or_else(|| Shader::SolidColor(skia_color(start_color))),
+        };
+
+        let paint = Paint {
+            shader,
+            anti_alias: true,
+            ..Default::default()
+        };
+
+        let mask = self.clip.as_ref().map(|(_, mask)| mask);
+        self.pixmap
+            .fill_path(path, &paint, FillRule::Winding, Transform::identity(), mask);
+    }
+
+    /// Blends a solid color into a pixel, with `coverage` in `0..=1`
+    fn blend_pixel(&mut self, x: i32, y: i32, color: Color, coverage: f32) {
+        let (width, height) = (self.pixmap.width() as i32, self.pixmap.height() as i32);
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return;
+        }
+
+        if let Some((ref clip, _)) = self.clip {
+            if !clip.contains(vec2(x as f32 + 0.5, y as f32 + 0.5)) {
+                return;
+            }
+        }
+
+        let alpha = coverage.clamp(0.0, 1.0) * color.a as f32 / 255.0;
+        let pixel = &mut self.pixmap.pixels_mut()[(y * width + x) as usize];
+
+        let blend = |src: u8, dst: u8| -> u8 {
+            (src as f32 * alpha + dst as f32 * (1.0 - alpha)).round() as u8
+        };
+
+        let r = blend(color.r, pixel.red());
+        let g = blend(color.g, pixel.green());
+        let b = blend(color.b, pixel.blue());
+        let a = blend(255, pixel.alpha());
+
+        // Premultiplied components never exceed alpha, but rounding can push them over
+        *pixel = PremultipliedColorU8::from_rgba(r.min(a), g.min(a), b.min(a), a).unwrap();
+    }
+}
+
+fn skia_color(c: Color) -> tiny_skia::Color {
+    tiny_skia::Color::from_rgba8(c.r, c.g, c.b, c.a)
+}
+
+/// Same shape as NanoVG's `rounded_rect`, with the radius clamped to half the shorter side
+fn rounded_rect_path(pos: Vec2, size: Vec2, radius: f32) -> Option<Path> {
+    let (x, y, w, h) = (pos.x(), pos.y(), size.x(), size.y());
+    let r = radius.min(w.abs() * 0.5).min(h.abs() * 0.5).max(0.0);
+    // Distance of the bezier control points from the corners, for a circular arc
+    let k = r * (1.0 - 0.552_284_8);
+
+    let mut pb = PathBuilder::new();
+    pb.move_to(x + r, y);
+    pb.line_to(x + w - r, y);
+    pb.cubic_to(x + w - k, y, x + w, y + k, x + w, y + r);
+    pb.line_to(x + w, y + h - r);
+    pb.cubic_to(x + w, y + h - k, x + w - k, y + h, x + w - r, y + h);
+    pb.line_to(x + r, y + h);
+    pb.cubic_to(x + k, y + h, x, y + h - k, x, y + h - r);
+    pb.line_to(x, y + r);
+    pb.cubic_to(x, y + k, x + k, y, x + r, y);
+    pb.close();
+    pb.finish()
+}
+
+fn rect_path(pos: Vec2, size: Vec2) -> Option<Path> {
+    let rect = tiny_skia::Rect::from_xywh(pos.x(), pos.y(), size.x(), size.y())?;
+    Some(PathBuilder::from_rect(rect))
+}
+
+impl TextMeasure for SoftwareRenderer {
+    fn text_width(&mut self, text: &str, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        let mut width = 0.0;
+        let mut prev = None;
+
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(prev) = prev {
+                width += font.kern(prev, id);
+            }
+            width += font.h_advance(id);
+            prev = Some(id);
+        }
+
+        width
+    }
+
+    fn text_metrics(&mut self, size: f32) -> TextMetrics {
+        let font = self.font.as_scaled(PxScale::from(size));
+
+        TextMetrics {
+            ascent: font.ascent(),
+            descent: font.descent(),
+            line_height: font.height() + font.line_gap(),
+        }
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn fill_rect(&mut self, pos: Vec2, size: Vec2, fill: Fill) {
+        if let Some(path) = rect_path(pos, size) {
+            self.fill_path(&path, fill);
+        }
+    }
+
+    fn fill_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, fill: Fill) {
+        if let Some(path) = rounded_rect_path(pos, size, radius) {
+            self.fill_path(&path, fill);
+        }
+    }
+
+    fn stroke_rounded_rect(&mut self, pos: Vec2, size: Vec2, radius: f32, color: Color) {
+        if let Some(path) = rounded_rect_path(pos, size, radius) {
+            let paint = Paint {
+                shader: Shader::SolidColor(skia_color(color)),
+                anti_alias: true,
+                ..Default::default()
+            };
+
+            let mask = self.clip.as_ref().map(|(_, mask)| mask);
+            self.pixmap.stroke_path(
+                &path,
+                &paint,
+                &Stroke::default(),
+                Transform::identity(),
+                mask,
+            );
+        }
+    }
+
+    fn text(&mut self, pos: Vec2, text: &str, size: f32, color: Color) {
+        let font = self.font.as_scaled(PxScale::from(size));
+        // Vertically centered like NanoVG's `Alignment::middle`
+        let baseline = pos.y() + (font.ascent() + font.descent()) * 0.5;
+
+        let mut outlines = Vec::new();
+        let mut x = pos.x();
+        let mut prev = None;
+
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(prev) = prev {
+                x += font.kern(prev, id);
+            }
+
+            let glyph = id.with_scale_and_position(size, ab_glyph::point(x, baseline));
+            outlines.extend(self.font.outline_glyph(glyph));
+
+            x += font.h_advance(id);
+            prev = Some(id);
+        }
+
+        for outline in outlines {
+            let bounds = outline.px_bounds();
+            let mut coverage = Vec::new();
+            outline.draw(|gx, gy, c| coverage.push((gx, gy, c)));
+
+            for (gx, gy, c) in coverage {
+                let px = bounds.min.x as i32 + gx as i32;
+                let py = bounds.min.y as i32 + gy as i32;
+                self.blend_pixel(px, py, color, c);
+            }
+        }
+    }
+
+    fn clip(&self) -> Option<Rect> {
+        self.clip.as_ref().map(|(rect, _)| *rect)
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip.map(|rect| {
+            let mut mask = Mask::new(self.pixmap.width(), self.pixmap.height()).unwrap();
+            // Empty clips leave the mask empty, so that nothing is drawn
+            if let Some(path) = rect_path(rect.pos, rect.size) {
+                mask.fill_path(&path, FillRule::Winding, false, Transform::identity());
+            }
+            (rect, mask)
+        });
+    }
+}
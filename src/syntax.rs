@@ -0,0 +1,99 @@
+use crate::ast::{FileId, Node};
+use crate::grammar;
+use crate::lexer::{LexError, LexErrorKind, Spanned, Tok};
+
+/// What went wrong in a .grui file, short of an unexpected token
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxErrorKind {
+    /// The text could not be split into tokens
+    Lex(LexErrorKind),
+    InvalidUid(String),
+    InvalidColor(String),
+    DuplicateProperty(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub location: usize,
+    pub kind: SyntaxErrorKind,
+}
+
+impl From<LexError> for SyntaxError {
+    fn from(err: LexError) -> Self {
+        Self {
+            location: err.location,
+            kind: SyntaxErrorKind::Lex(err.kind),
+        }
+    }
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            SyntaxErrorKind::Lex(ref kind) => LexError {
+                location: self.location,
+                kind: kind.clone(),
+            }
+            .fmt(f),
+            SyntaxErrorKind::InvalidUid(ref s) => write!(f, "invalid widget uid `#{}`", s),
+            SyntaxErrorKind::DuplicateProperty(ref s) => write!(f, "duplicate property `{}`", s),
+            SyntaxErrorKind::InvalidColor(ref s) => write!(
+                f,
+                "invalid color `#{}`; expected 3, 4, 6 or 8 hex digits",
+                s
+            ),
+        }
+    }
+}
+
+pub type ParseError<'input> = lalrpop_util::ParseError<usize, Tok<'input>, SyntaxError>;
+
+/// Parses the tokens of a .grui file, such as those of a `Lexer`
+pub fn parse<'input>(
+    file: FileId,
+    tokens: impl IntoIterator<Item = Spanned<'input>>,
+) -> Result<Vec<Node>, ParseError<'input>> {
+    let tokens = tokens.into_iter().map(|tok| tok.map_err(SyntaxError::from));
+    grammar::MainParser::new().parse(file, tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn error(text: &str) -> SyntaxError {
+        match parse(FileId::default(), Lexer::new(text)) {
+            Err(lalrpop_util::ParseError::User { error }) => error,
+            res => panic!("expected a syntax error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn errors_come_from_the_lexer_or_the_grammar() {
+        let err = error("label \"x\" (width: 1, width: 2);");
+        assert_eq!(
+            err.kind,
+            SyntaxErrorKind::DuplicateProperty("width".to_owned())
+        );
+        assert_eq!(err.location, 21);
+        assert_eq!(
+            error("#1a label;").kind,
+            SyntaxErrorKind::InvalidUid("1a".to_owned())
+        );
+        assert_eq!(
+            error("label (color: #12);").kind,
+            SyntaxErrorKind::InvalidColor("12".to_owned())
+        );
+
+        let err = error("label \"x\" (width: 3em);");
+        assert_eq!(
+            err.kind,
+            SyntaxErrorKind::Lex(LexErrorKind::InvalidNumberSuffix("em".to_owned()))
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid number suffix `em`; expected `px` or `%`"
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use crate::ast::{self, Node};
+use crate::diagnostics::{Diagnostic, SourceMap};
+
+use std::collections::HashMap;
+
+/// Removes top-level `template` declarations from `nodes`, and replaces their instantiations
+/// with copies of the template bodies, parameters substituted by the positional values.
+///
+/// Invalid instantiations are reported and left out of the result.
+pub fn expand_templates(nodes: Vec<Node>, sources: &SourceMap) -> (Vec<Node>, Vec<Diagnostic>) {
+    let mut templates: HashMap<String, ast::Template> = HashMap::new();
+    let mut diagnostics = Vec::new();
+    let mut rest = Vec::new();
+
+    for node in nodes {
+        match node {
+            Node::Template(template) => {
+                if templates.contains_key(&template.name) {
+                    diagnostics.push(sources.diagnostic(
+                        template.span,
+                        format!("template `{}` is already declared", template.name),
+                    ));
+                } else {
+                    templates.insert(template.name.clone(), template);
+                }
+            }
+            node => rest.push(node),
+        }
+    }
+
+    let mut expander = TemplateExpander {
+        templates: &templates,
+        sources,
+        diagnostics,
+        stack: Vec::new(),
+    };
+
+    let nodes = expander.expand(&rest);
+    (nodes, expander.diagnostics)
+}
+
+struct TemplateExpander<'a> {
+    templates: &'a HashMap<String, ast::Template>,
+    sources: &'a SourceMap,
+    diagnostics: Vec<Diagnostic>,
+    /// Names of templates currently being instantiated, for recursion detection
+    stack: Vec<&'a str>,
+}
+
+impl<'a> TemplateExpander<'a> {
+    fn error(&mut self, span: ast::Span, message: String) {
+        self.diagnostics
+            .push(self.sources.diagnostic(span, message));
+    }
+
+    fn expand(&mut self, nodes: &[Node]) -> Vec<Node> {
+        let templates = self.templates;
+        let mut result = Vec::with_capacity(nodes.len());
+
+        for node in nodes {
+            match node {
+                Node::Item(item) => match templates.get(&item.ident) {
+                    Some(template) => result.append(&mut self.instantiate(template, item)),
+                    None => {
+                        let mut item = item.clone();
+                        item.children = item.children.map(|ch| self.expand(&ch));
+                        result.push(Node::Item(item));
+                    }
+                },
+                Node::If(cond) => {
+                    let mut cond = cond.clone();
+                    cond.body = self.expand(&cond.body);
+                    cond.else_body = cond.else_body.map(|body| self.expand(&body));
+                    result.push(Node::If(cond));
+                }
+                Node::For(each) => {
+                    let mut each = each.clone();
+                    each.body = self.expand(&each.body);
+                    result.push(Node::For(each));
+                }
+                node => result.push(node.clone()),
+            }
+        }
+
+        result
+    }
+
+    fn instantiate(&mut self, template: &'a ast::Template, item: &ast::Item) -> Vec<Node> {
+        if self.stack.contains(&template.name.as_str()) {
+            let mut chain = self.stack.clone();
+            chain.push(&template.name);
+            self.error(
+                item.span,
+                format!("recursive template instantiation: {}", chain.join(" -> ")),
+            );
+            return Vec::new();
+        }
+
+        if item.values.len() != template.params.len() {
+            self.error(
+                item.span,
+                format!(
+                    "template `{}` takes {} value(s), found {}",
+                    template.name,
+                    template.params.len(),
+                    item.values.len()
+                ),
+            );
+            return Vec::new();
+        }
+
+        if item.children.is_some() {
+            self.error(
+                item.span,
+                format!(
+                    "template `{}` does not take a block of children",
+                    template.name
+                ),
+            );
+            return Vec::new();
+        }
+
+        let args: HashMap<&str, &ast::Value> = template
+            .params
+            .iter()
+            .map(String::as_str)
+            .zip(&item.values)
+            .collect();
+
+        let mut body = substitute_nodes(&template.body, &args);
+
+        // The uid and properties of the instantiation go to the template's root item
+        if item.uid.is_some() || !item.properties.is_empty() {
+            match body.as_mut_slice() {
+                [Node::Item(root)] => {
+                    if item.uid.is_some() {
+                        root.uid = item.uid.clone();
+                    }
+
+                    for prop in &item.properties.0 {
+                        match root.properties.0.iter_mut().find(|p| p.name == prop.name) {
+                            Some(existing) => *existing = prop.clone(),
+                            None => root.properties.0.push(prop.clone()),
+                        }
+                    }
+                }
+                _ => {
+                    self.error(
+                        item.span,
+                        format!(
+                            "template `{}` must have a single root item to take a uid or properties",
+                            template.name
+                        ),
+                    );
+                    return Vec::new();
+                }
+            }
+        }
+
+        self.stack.push(&template.name);
+        let mut result = self.expand(&body);
+        self.stack.pop();
+
+        stamp_instance(&mut result, item.span);
+        result
+    }
+}
+
+/// Records `instance` on the `if` and `for` blocks in `nodes`, which emission hashes into
+/// the ids of the widgets inside them
+fn stamp_instance(nodes: &mut [Node], instance: ast::Span) {
+    for node in nodes {
+        match node {
+            Node::Item(item) => {
+                if let Some(ref mut children) = item.children {
+                    stamp_instance(children, instance);
+                }
+            }
+            Node::If(cond) => {
+                cond.instances.push(instance);
+                stamp_instance(&mut cond.body, instance);
+                if let Some(ref mut body) = cond.else_body {
+                    stamp_instance(body, instance);
+                }
+            }
+            Node::For(each) => {
+                each.instances.push(instance);
+                stamp_instance(&mut each.body, instance);
+            }
+            Node::Include(_) | Node::Template(_) => (),
+        }
+    }
+}
+
+fn substitute_value(value: &ast::Value, args: &HashMap<&str, &ast::Value>) -> ast::Value {
+    match value {
+        ast::Value::Ident(ident) => args
+            .get(ident.as_str())
+            .map(|arg| (*arg).clone())
+            .unwrap_or_else(|| value.clone()),
+        _ => value.clone(),
+    }
+}
+
+fn substitute_nodes(nodes: &[Node], args: &HashMap<&str, &ast::Value>) -> Vec<Node> {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Item(item) => {
+                let mut item = item.clone();
+
+                for value in item.values.iter_mut() {
+                    *value = substitute_value(value, args);
+                }
+
+                for prop in item.properties.0.iter_mut() {
+                    prop.value = substitute_value(&prop.value, args);
+                }
+
+                item.children = item.children.map(|ch| substitute_nodes(&ch, args));
+                Node::Item(item)
+            }
+            Node::If(cond) => {
+                let mut cond = cond.clone();
+                cond.body = substitute_nodes(&cond.body, args);
+                cond.else_body = cond.else_body.map(|body| substitute_nodes(&body, args));
+                Node::If(cond)
+            }
+            Node::For(each) => {
+                let mut each = each.clone();
+                each.body = substitute_nodes(&each.body, args);
+                Node::For(each)
+            }
+            node => node.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,798 @@
+use crate::ast;
+use crate::grid::{GridSpec, MAX_GRID_LINE};
+use crate::layout::{Align, Justify, Size};
+use crate::scroll::ScrollDrag;
+
+use glam::Vec2;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct WidgetId(pub(crate) usize);
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct WidgetUid(pub(crate) Vec<WidgetId>);
+
+#[derive(Debug)]
+pub enum WidgetEvent {
+    BeginHover,
+    EndHover,
+    BeginActive,
+    EndActive,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EventPropagation {
+    Continue,
+    Stop,
+}
+
+pub trait WidgetBehavior {
+    fn handle_event(
+        &mut self,
+        event: &WidgetEvent,
+        api: &mut dyn WidgetBehaviorApi,
+    ) -> EventPropagation;
+}
+
+pub trait WidgetBehaviorApi {
+    fn post_response(&mut self, r: WidgetResponse);
+}
+
+pub struct GenericWidget {
+    pub draw_style: &'static str,
+    pub behaviors: Vec<Box<dyn WidgetBehavior>>,
+}
+
+#[derive(Default)]
+pub struct ClickableBehavior {
+    hover: bool,
+    active: bool,
+}
+
+impl WidgetBehavior for ClickableBehavior {
+    fn handle_event(
+        &mut self,
+        event: &WidgetEvent,
+        api: &mut dyn WidgetBehaviorApi,
+    ) -> EventPropagation {
+        match event {
+            WidgetEvent::BeginHover => self.hover = true,
+            WidgetEvent::EndHover => self.hover = false,
+            WidgetEvent::BeginActive => self.active = true,
+            WidgetEvent::EndActive => {
+                /*if self.hover*/
+                {
+                    api.post_response(WidgetResponse::Activated);
+                }
+
+                self.active = false;
+            }
+        }
+
+        EventPropagation::Continue
+    }
+}
+
+impl std::fmt::Debug for GenericWidget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GenericWidget")
+    }
+}
+
+#[derive(Debug)]
+pub enum Widget {
+    Generic(GenericWidget),
+    Button(String),
+    Label(String),
+    Horizontal,
+    Vertical,
+    Grid(GridSpec),
+    /// Stacks its children vertically like `Vertical`, showing the part of them which fits
+    /// and scrolling to the rest. Its natural size fits all of its children, so it needs a
+    /// `height`, a `max_height` or `shrink` to be smaller than them.
+    Scroll,
+    /// Puts its children over each other, later ones on top unless they have a `z_index`
+    Stack,
+}
+
+/// Per-widget attributes, typically coming from the `(name: value, ...)` list in .grui files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WidgetProps {
+    /// Fixed size, replacing the size of the content
+    pub width: Option<Size>,
+    pub height: Option<Size>,
+    pub min_width: Option<f32>,
+    pub max_width: Option<f32>,
+    pub min_height: Option<f32>,
+    pub max_height: Option<f32>,
+    /// Space between the edges and the content, on every side. Widgets which draw a
+    /// background have their own default.
+    pub padding: Option<f32>,
+    /// Share of the parent's leftover space the widget takes along the parent's main axis
+    pub grow: f32,
+    /// Share of the parent's missing space the widget gives up, weighted by its size
+    pub shrink: f32,
+    /// Space between the children of a container
+    pub gap: f32,
+    pub justify: Justify,
+    pub align: Align,
+    /// Cell of a grid the widget goes in, counting from 1. Widgets without one fill the
+    /// first free cells, row by row.
+    pub row: Option<usize>,
+    pub column: Option<usize>,
+    /// Number of rows and columns of a grid the widget covers
+    pub row_span: usize,
+    pub column_span: usize,
+    /// Placement inside a grid cell, horizontally. Widgets stretch to fill the cell by default.
+    pub justify_self: Option<Align>,
+    /// Placement inside a grid cell vertically, or across a box, replacing the box's `align`
+    pub align_self: Option<Align>,
+    /// Offsets from the edges of the parent. Widgets with any of them are placed by them,
+    /// on top of the parent's layout instead of taking part in it.
+    pub left: Option<f32>,
+    pub top: Option<f32>,
+    pub right: Option<f32>,
+    pub bottom: Option<f32>,
+    /// Drawing order among the widget's siblings: higher is drawn on top, and gets the mouse
+    /// first. Widgets with the same `z_index` are drawn in order.
+    pub z_index: i32,
+    pub enabled: bool,
+    pub tooltip: Option<String>,
+    /// Background of buttons, or text of labels, replacing the default one
+    pub color: Option<ast::Color>,
+}
+
+impl Default for WidgetProps {
+    fn default() -> Self {
+        Self {
+            width: None,
+            height: None,
+            min_width: None,
+            max_width: None,
+            min_height: None,
+            max_height: None,
+            padding: None,
+            grow: 0.0,
+            shrink: 0.0,
+            gap: 0.0,
+            justify: Justify::Start,
+            align: Align::Start,
+            row: None,
+            column: None,
+            row_span: 1,
+            column_span: 1,
+            justify_self: None,
+            align_self: None,
+            left: None,
+            top: None,
+            right: None,
+            bottom: None,
+            z_index: 0,
+            enabled: true,
+            tooltip: None,
+            color: None,
+        }
+    }
+}
+
+fn size_from_ast(value: &ast::Value) -> Option<Size> {
+    match *value {
+        ast::Value::Dimension(v, ast::Unit::Percent) => Some(Size::Percent(v)),
+        ref value => value.as_f32().map(Size::Px),
+    }
+}
+
+impl WidgetProps {
+    pub fn from_ast(props: &ast::Properties) -> Self {
+        let default = Self::default();
+        let number = |name| props.get(name).and_then(ast::Value::as_f32);
+        let keyword = |name| props.get(name).and_then(ast::Value::as_ident);
+        let count = |name| number(name).map(|v| v.clamp(1.0, MAX_GRID_LINE as f32) as usize);
+
+        Self {
+            width: props.get("width").and_then(size_from_ast),
+            height: props.get("height").and_then(size_from_ast),
+            min_width: props.get("min_width").and_then(ast::Value::as_f32),
+            max_width: props.get("max_width").and_then(ast::Value::as_f32),
+            min_height: props.get("min_height").and_then(ast::Value::as_f32),
+            max_height: props.get("max_height").and_then(ast::Value::as_f32),
+            padding: props.get("padding").and_then(ast::Value::as_f32),
+            grow: number("grow").unwrap_or(default.grow),
+            shrink: number("shrink").unwrap_or(default.shrink),
+            gap: number("gap").unwrap_or(default.gap),
+            justify: keyword("justify")
+                .and_then(Justify::from_keyword)
+                .unwrap_or(default.justify),
+            align: keyword("align")
+                .and_then(Align::from_keyword)
+                .unwrap_or(default.align),
+            row: count("row"),
+            column: count("column"),
+            row_span: count("row_span").unwrap_or(default.row_span),
+            column_span: count("column_span").unwrap_or(default.column_span),
+            justify_self: keyword("justify_self").and_then(Align::from_keyword),
+            align_self: keyword("align_self").and_then(Align::from_keyword),
+            left: number("left"),
+            top: number("top"),
+            right: number("right"),
+            bottom: number("bottom"),
+            z_index: number("z_index").map_or(default.z_index, |z| z as i32),
+            enabled: props
+                .get("enabled")
+                .and_then(ast::Value::as_bool)
+                .unwrap_or(default.enabled),
+            tooltip: props
+                .get("tooltip")
+                .and_then(ast::Value::as_str)
+                .map(str::to_owned),
+            color: props.get("color").and_then(ast::Value::as_color),
+        }
+    }
+
+    /// Whether the widget is placed by its offsets, rather than by its parent's layout
+    pub fn is_positioned(&self) -> bool {
+        self.left.is_some() || self.top.is_some() || self.right.is_some() || self.bottom.is_some()
+    }
+}
+
+#[derive(Debug)]
+pub struct UiNode {
+    pub widget: Widget,
+    pub props: WidgetProps,
+    /// The `#name` given to the widget in .grui files, for finding it with `Ui::id`
+    pub string_uid: Option<String>,
+    pub children: Vec<(WidgetId, UiNode)>,
+    pub(crate) next_child_id: WidgetId,
+    /// Whether the node or anything below it changed in the last `reconcile`, which lets
+    /// `LayoutCache` skip the subtrees which didn't. New nodes count as changed.
+    pub(crate) changed: bool,
+}
+
+impl From<Widget> for UiNode {
+    fn from(w: Widget) -> Self {
+        UiNode::new(w)
+    }
+}
+
+/// How the uids of the widgets kept by `UiNode::reconcile` changed, so that state kept by
+/// uid can follow the widgets it belongs to
+#[derive(Debug, Default)]
+pub struct UidChanges {
+    /// Old and new uids of the kept widgets whose uid changed, including every descendant of
+    /// a widget which moved
+    moved: HashMap<WidgetUid, WidgetUid>,
+    /// Widgets which are gone, along with their descendants
+    removed: Vec<WidgetUid>,
+}
+
+impl UidChanges {
+    /// The uid a widget of the previous frame has now, or `None` if it is gone
+    pub fn get(&self, uid: WidgetUid) -> Option<WidgetUid> {
+        if let Some(new) = self.moved.get(&uid) {
+            return Some(new.clone());
+        }
+
+        if self
+            .removed
+            .iter()
+            .any(|removed| uid.0.starts_with(&removed.0))
+        {
+            None
+        } else {
+            Some(uid)
+        }
+    }
+}
+
+pub(crate) struct UiContext<'a> {
+    pub(crate) uid: WidgetUid,
+    pub(crate) interaction_state: &'a UiInteractionState,
+}
+
+impl<'a> UiContext<'a> {
+    pub(crate) fn nested(&self, id: WidgetId) -> Self {
+        let mut uid = self.uid.clone();
+        uid.0.push(id);
+
+        Self {
+            uid,
+            interaction_state: self.interaction_state,
+        }
+    }
+}
+
+/// A handle to a node of the UI tree being built, for adding widgets and querying them.
+pub struct Ui<'a, 'b> {
+    pub(crate) node: &'a mut UiNode,
+    pub(crate) context: UiContext<'b>,
+}
+
+#[derive(Debug)]
+pub struct WidgetNotFoundError;
+
+impl Error for WidgetNotFoundError {}
+impl std::fmt::Display for WidgetNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not find widget")
+    }
+}
+
+pub type UiResult<T> = Result<T, Box<dyn Error>>;
+
+impl UiNode {
+    pub fn new(widget: Widget) -> Self {
+        Self {
+            widget,
+            props: Default::default(),
+            string_uid: None,
+            children: Vec::new(),
+            next_child_id: WidgetId(0),
+            changed: true,
+        }
+    }
+
+    /// Appends a child with the next sequential id, like `Ui::append`, for building trees
+    /// outside of a frame.
+    pub fn push(&mut self, child: impl Into<UiNode>) -> WidgetId {
+        let id = self.next_child_id;
+        self.next_child_id.0 += 1;
+        self.children.push((id, child.into()));
+        id
+    }
+
+    /// Updates a tree kept from the previous frame to match the one just emitted, keeping the
+    /// nodes which are still there, along with the state of their behaviors. Children are
+    /// matched by their `string_uid` and id, or failing that by their `string_uid` alone, in
+    /// order, so that named widgets can move around. Nodes are only kept if they are still
+    /// the same kind of widget. Generic widgets keep their behaviors as long as their draw
+    /// style and number of behaviors stay the same.
+    ///
+    /// Kept nodes take the ids they were emitted with, and the returned changes tell where
+    /// the nodes of the previous frame went. Nodes are marked with whether they changed, for
+    /// the next `LayoutCache::layout`.
+    pub fn reconcile(&mut self, emitted: UiNode) -> UidChanges {
+        let mut changes = UidChanges::default();
+        self.reconcile_at(emitted, &mut Vec::new(), &mut Vec::new(), &mut changes);
+        changes
+    }
+
+    /// `old_uid` and `new_uid` are the uids of this node in the previous frame and in this one
+    fn reconcile_at(
+        &mut self,
+        emitted: UiNode,
+        old_uid: &mut Vec<WidgetId>,
+        new_uid: &mut Vec<WidgetId>,
+        changes: &mut UidChanges,
+    ) {
+        let UiNode {
+            widget,
+            props,
+            string_uid,
+            children,
+            next_child_id,
+            changed: _,
+        } = emitted;
+
+        let mut changed = false;
+        match (&mut self.widget, widget) {
+            (Widget::Generic(old), Widget::Generic(new))
+                if old.draw_style == new.draw_style
+                    && old.behaviors.len() == new.behaviors.len() => {}
+            (old, new) if std::mem::discriminant(old) == std::mem::discriminant(&new) => {
+                changed = match (&*old, &new) {
+                    (Widget::Label(old), Widget::Label(new))
+                    | (Widget::Button(old), Widget::Button(new)) => old != new,
+                    (Widget::Grid(old), Widget::Grid(new)) => old != new,
+                    _ => false,
+                };
+                *old = new;
+            }
+            (_, new) => {
+                // A different kind of widget, which starts from scratch
+                changes.removed.push(WidgetUid(old_uid.clone()));
+                *self = UiNode {
+                    widget: new,
+                    props,
+                    string_uid,
+                    children,
+                    next_child_id,
+                    changed: true,
+                };
+                return;
+            }
+        }
+
+        if old_uid != new_uid {
+            changes
+                .moved
+                .insert(WidgetUid(old_uid.clone()), WidgetUid(new_uid.clone()));
+            // The layout cache finds nodes by id, so it has nothing for them where they are now
+            changed = true;
+        }
+
+        changed |= self.props != props;
+        changed |= self.children.len() != children.len()
+            || self
+                .children
+                .iter()
+                .zip(&children)
+                .any(|((old_id, _), (id, _))| old_id != id);
+        self.props = props;
+        self.string_uid = string_uid;
+        self.next_child_id = next_child_id;
+
+        let old = std::mem::take(&mut self.children);
+        let mut taken = vec![false; old.len()];
+
+        // Children mostly stay in place. Where they don't, they are looked up by name and id,
+        // with the first of several old children with the same key going to the first new one.
+        let mut by_key = None;
+        let mut found: Vec<Option<usize>> = Vec::with_capacity(children.len());
+        for (i, (id, new)) in children.iter().enumerate() {
+            let key = (new.string_uid.as_deref(), *id);
+            found.push(match old.get(i) {
+                Some((old_id, child))
+                    if !taken[i] && (child.string_uid.as_deref(), *old_id) == key =>
+                {
+                    taken[i] = true;
+                    Some(i)
+                }
+                _ => by_key
+                    .get_or_insert_with(|| {
+                        index_children(&old, |id, child| Some((child.string_uid.as_deref(), id)))
+                    })
+                    .get_mut(&key)
+                    .and_then(|same| take_first(same, &mut taken)),
+            });
+        }
+
+        // Then named children whose id changed, such as when widgets were added before them
+        if found
+            .iter()
+            .zip(&children)
+            .any(|(found, (_, new))| found.is_none() && new.string_uid.is_some())
+        {
+            let mut by_name = index_children(&old, |_, child| child.string_uid.as_deref());
+            for (found, (_, new)) in found.iter_mut().zip(&children) {
+                if let (None, Some(name)) = (*found, new.string_uid.as_deref()) {
+                    *found = by_name
+                        .get_mut(name)
+                        .and_then(|same| take_first(same, &mut taken));
+                }
+            }
+        }
+
+        let mut old: Vec<Option<(WidgetId, UiNode)>> = old.into_iter().map(Some).collect();
+        self.children = children
+            .into_iter()
+            .zip(found)
+            .map(
+                |((id, new), found)| match found.and_then(|i| old[i].take()) {
+                    Some((old_id, mut child)) => {
+                        old_uid.push(old_id);
+                        new_uid.push(id);
+                        child.reconcile_at(new, old_uid, new_uid, changes);
+                        old_uid.pop();
+                        new_uid.pop();
+                        (id, child)
+                    }
+                    None => (id, new),
+                },
+            )
+            .collect();
+        self.changed = changed || self.children.iter().any(|(_, child)| child.changed);
+
+        for (id, _) in old.into_iter().flatten() {
+            old_uid.push(id);
+            changes.removed.push(WidgetUid(old_uid.clone()));
+            old_uid.pop();
+        }
+    }
+
+    pub(crate) fn id<'a>(
+        &'a mut self,
+        label: &str,
+        uid_prefix: &WidgetUid,
+    ) -> UiResult<(WidgetUid, &'a mut UiNode)> {
+        if let Some(ref s) = self.string_uid {
+            if s == label {
+                return Ok((uid_prefix.clone(), self));
+            }
+        }
+
+        for (id, ch) in self.children.iter_mut() {
+            let mut uid_prefix = uid_prefix.clone();
+            uid_prefix.0.push(*id);
+
+            if let Ok(res) = ch.id(label, &uid_prefix) {
+                return Ok(res);
+            }
+        }
+
+        Err(Box::new(WidgetNotFoundError))
+    }
+}
+
+/// Removes the first position of `same` which isn't `taken` yet, and takes it
+fn take_first(same: &mut Vec<usize>, taken: &mut [bool]) -> Option<usize> {
+    let i = std::iter::from_fn(|| same.pop()).find(|&i| !taken[i])?;
+    taken[i] = true;
+    Some(i)
+}
+
+/// Positions of `children` by their key, each list in reverse order so that popping from it
+/// gives the first one
+fn index_children<'a, K: Hash + Eq>(
+    children: &'a [(WidgetId, UiNode)],
+    key: impl Fn(WidgetId, &'a UiNode) -> Option<K>,
+) -> HashMap<K, Vec<usize>> {
+    let mut index: HashMap<K, Vec<usize>> = HashMap::new();
+    for (i, (id, child)) in children.iter().enumerate().rev() {
+        if let Some(key) = key(*id, child) {
+            index.entry(key).or_default().push(i);
+        }
+    }
+    index
+}
+
+impl<'a, 'b> Ui<'a, 'b> {
+    pub(crate) fn new(node: &'a mut UiNode, context: UiContext<'b>) -> Self {
+        Self { node, context }
+    }
+
+    /// The node this handle points at, with everything added to it so far
+    pub fn node(&self) -> &UiNode {
+        self.node
+    }
+
+    pub fn clicked(&self) -> bool {
+        /*self.context.interaction_state.mouse_released
+        && Some(&self.context.uid) == self.context.interaction_state.hover_widget.as_ref()
+        && Some(&self.context.uid) == self.context.interaction_state.drag_begin_widget.as_ref()*/
+        self.context
+            .interaction_state
+            .widget_responses
+            .get(&self.context.uid)
+            .map(|responses| responses.contains(&WidgetResponse::Activated))
+            .unwrap_or_default()
+    }
+
+    pub fn id(&mut self, label: &str) -> UiResult<Ui<'_, '_>> {
+        let interaction_state = self.context.interaction_state;
+
+        self.node.id(label, &self.context.uid).map(|(uid, n)| {
+            Ui::new(
+                n,
+                UiContext {
+                    interaction_state,
+                    uid,
+                },
+            )
+        })
+    }
+
+    pub fn append<'s, 'r>(&'s mut self, child: impl Into<UiNode>) -> Ui<'r, 'b>
+    where
+        's: 'r,
+    {
+        let id = self.node.next_child_id;
+        self.node.next_child_id.0 += 1;
+        self.append_with_id(id, child)
+    }
+
+    /// Appends a child with an id chosen by the caller, leaving the sequential ids untouched.
+    pub(crate) fn append_with_id<'s, 'r>(
+        &'s mut self,
+        id: WidgetId,
+        child: impl Into<UiNode>,
+    ) -> Ui<'r, 'b>
+    where
+        's: 'r,
+    {
+        self.node.children.push((id, child.into()));
+
+        let len = self.node.children.len();
+        let last = &mut self.node.children[len - 1];
+
+        Ui {
+            node: &mut last.1,
+            context: self.context.nested(last.0),
+        }
+    }
+
+    // syntax sugar
+    pub fn button(&mut self, label: &str) -> Ui<'_, '_> {
+        let mut res = self.append(Widget::Generic(GenericWidget {
+            draw_style: "button",
+            behaviors: vec![Box::new(ClickableBehavior::default())],
+        }));
+        res.label(label);
+        res
+    }
+
+    // syntax sugar
+    pub fn label(&mut self, label: &str) -> Ui<'_, '_> {
+        self.append(Widget::Label(label.to_owned()))
+    }
+}
+
+/*
+// syntax sugar
+fn button<'b, 's, 'r>(ui: &'s mut Ui<'_, 'b>, label: &str) -> Ui<'r, 'b>
+where
+    's: 'r,
+{
+    ui.append(Widget::Button(label.to_owned()))
+}
+
+// syntax sugar
+fn label<'b, 's, 'r>(ui: &'s mut Ui<'_, 'b>, label: &str) -> Ui<'r, 'b>
+where
+    's: 'r,
+{
+    ui.append(Widget::Label(label.to_owned()))
+}
+*/
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WidgetResponse {
+    Activated,
+}
+
+impl WidgetBehaviorApi for Vec<WidgetResponse> {
+    fn post_response(&mut self, r: WidgetResponse) {
+        self.push(r);
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct UiInteractionState {
+    pub(crate) hover_widget: Option<WidgetUid>,
+    pub(crate) drag_begin_widget: Option<WidgetUid>,
+    pub(crate) mouse_down: bool,
+    pub(crate) mouse_released: bool,
+    pub(crate) mouse_pressed: bool,
+    pub(crate) widget_responses: HashMap<WidgetUid, Vec<WidgetResponse>>,
+    /// How far the content of each scroll container is scrolled, kept across frames
+    pub(crate) scroll_offsets: HashMap<WidgetUid, Vec2>,
+    pub(crate) scroll_drag: Option<ScrollDrag>,
+}
+
+impl UiInteractionState {
+    /// The enabled button under the mouse, as of the last frame
+    pub fn hover_widget(&self) -> Option<&WidgetUid> {
+        self.hover_widget.as_ref()
+    }
+
+    /// Moves the state kept for widgets along with them after `UiNode::reconcile`, and forgets
+    /// the widgets which are gone
+    pub(crate) fn follow_widgets(&mut self, changes: &UidChanges) {
+        self.scroll_offsets = std::mem::take(&mut self.scroll_offsets)
+            .into_iter()
+            .filter_map(|(uid, offset)| Some((changes.get(uid)?, offset)))
+            .collect();
+        self.hover_widget = self.hover_widget.take().and_then(|uid| changes.get(uid));
+        self.drag_begin_widget = self
+            .drag_begin_widget
+            .take()
+            .and_then(|uid| changes.get(uid));
+        self.scroll_drag = self.scroll_drag.take().and_then(|mut drag| {
+            drag.widget = changes.get(drag.widget)?;
+            Some(drag)
+        });
+    }
+
+    /// How far the content of a scroll container is scrolled, as of the last frame
+    pub fn scroll_offset(&self, widget: &WidgetUid) -> Vec2 {
+        self.scroll_offsets
+            .get(widget)
+            .copied()
+            .unwrap_or_else(Vec2::zero)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::vec2;
+
+    fn button(name: &str) -> UiNode {
+        let mut node = UiNode::new(Widget::Generic(GenericWidget {
+            draw_style: "button",
+            behaviors: vec![Box::new(ClickableBehavior::default())],
+        }));
+        node.string_uid = Some(name.to_owned());
+        node
+    }
+
+    fn tree(children: Vec<(usize, UiNode)>) -> UiNode {
+        let mut node = UiNode::new(Widget::Vertical);
+        node.children = children
+            .into_iter()
+            .map(|(id, child)| (WidgetId(id), child))
+            .collect();
+        node
+    }
+
+    /// Where the behaviors of each child live, which stays the same for kept nodes
+    fn behaviors(node: &UiNode) -> Vec<*const u8> {
+        node.children
+            .iter()
+            .map(|(_, child)| match child.widget {
+                Widget::Generic(ref w) => &*w.behaviors[0] as *const dyn WidgetBehavior as _,
+                _ => std::ptr::null(),
+            })
+            .collect()
+    }
+
+    fn uid(ids: &[usize]) -> WidgetUid {
+        WidgetUid(ids.iter().copied().map(WidgetId).collect())
+    }
+
+    #[test]
+    fn named_widgets_keep_their_state_when_reordered() {
+        let mut retained = tree(vec![(0, button("a")), (1, button("b"))]);
+        let before = behaviors(&retained);
+
+        let changes = retained.reconcile(tree(vec![(0, button("b")), (1, button("a"))]));
+
+        assert_eq!(behaviors(&retained), [before[1], before[0]]);
+        assert_eq!(changes.get(uid(&[0])), Some(uid(&[1])));
+        assert_eq!(changes.get(uid(&[1])), Some(uid(&[0])));
+    }
+
+    #[test]
+    fn repeated_names_are_told_apart_by_id() {
+        // Such as a named widget in a `for` block, whose ids come from the list keys
+        let mut retained = tree(vec![(10, button("row")), (20, button("row"))]);
+        let before = behaviors(&retained);
+
+        let changes = retained.reconcile(tree(vec![(20, button("row")), (10, button("row"))]));
+        assert_eq!(behaviors(&retained), [before[1], before[0]]);
+        assert_eq!(changes.get(uid(&[10])), Some(uid(&[10])));
+
+        // With new ids, they are matched in order
+        let before = behaviors(&retained);
+        let changes = retained.reconcile(tree(vec![
+            (30, button("row")),
+            (40, button("row")),
+            (50, button("row")),
+        ]));
+        let after = behaviors(&retained);
+        assert_eq!(after[..2], before[..]);
+        assert!(!before.contains(&after[2]));
+        assert_eq!(changes.get(uid(&[20])), Some(uid(&[30])));
+        assert_eq!(changes.get(uid(&[10])), Some(uid(&[40])));
+    }
+
+    #[test]
+    fn state_follows_widgets_and_is_dropped_with_them() {
+        let mut retained = tree(vec![
+            (0, button("a")),
+            (1, tree(vec![(0, UiNode::new(Widget::Scroll))])),
+            (2, UiNode::new(Widget::Scroll)),
+        ]);
+        let mut state = UiInteractionState::default();
+        state.scroll_offsets.insert(uid(&[1, 0]), vec2(0.0, 10.0));
+        state.scroll_offsets.insert(uid(&[2]), vec2(0.0, 20.0));
+        state.drag_begin_widget = Some(uid(&[0]));
+
+        // A new widget before the named one shifts its id, and the last one is gone
+        let mut nested = tree(vec![(0, UiNode::new(Widget::Scroll))]);
+        nested.string_uid = Some("list".to_owned());
+        retained.children[1].1.string_uid = Some("list".to_owned());
+        let changes = retained.reconcile(tree(vec![
+            (0, UiNode::new(Widget::Label("new".to_owned()))),
+            (1, button("a")),
+            (2, nested),
+        ]));
+        state.follow_widgets(&changes);
+
+        assert_eq!(state.drag_begin_widget, Some(uid(&[1])));
+        assert_eq!(state.scroll_offset(&uid(&[2, 0])), vec2(0.0, 10.0));
+        assert_eq!(state.scroll_offsets.len(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::ast;
+use crate::diagnostics::{Diagnostic, SourceMap};
+use crate::grid::check_cells;
+use crate::registry::{ValueKind, WidgetRegistry};
+
+/// Checks items against the signatures of the registered widgets, so that items which would
+/// be skipped during emission get reported instead of silently vanishing from the UI.
+pub fn validate_items(
+    registry: &WidgetRegistry,
+    sources: &SourceMap,
+    nodes: &[ast::Node],
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    validate_nodes(registry, sources, nodes, &mut diagnostics);
+    diagnostics
+}
+
+fn validate_nodes(
+    registry: &WidgetRegistry,
+    sources: &SourceMap,
+    nodes: &[ast::Node],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for node in nodes {
+        match node {
+            ast::Node::Item(item) => validate_item(registry, sources, item, diagnostics),
+            ast::Node::Include(include) => diagnostics
+                .push(sources.diagnostic(include.span, "include directive was not resolved")),
+            // Top-level templates are expanded by the loader, so this one must be nested
+            ast::Node::Template(template) => diagnostics.push(sources.diagnostic(
+                template.span,
+                "templates can only be declared at the top level of a file",
+            )),
+            ast::Node::If(cond) => {
+                validate_nodes(registry, sources, &cond.body, diagnostics);
+                if let Some(ref body) = cond.else_body {
+                    validate_nodes(registry, sources, body, diagnostics);
+                }
+            }
+            ast::Node::For(each) => validate_nodes(registry, sources, &each.body, diagnostics),
+        }
+    }
+}
+
+fn validate_item(
+    registry: &WidgetRegistry,
+    sources: &SourceMap,
+    item: &ast::Item,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut error = |span: ast::Span, message: String| {
+        diagnostics.push(sources.diagnostic(span, message));
+    };
+
+    let signature = match registry.signature(&item.ident) {
+        Some(signature) => signature,
+        None => {
+            error(item.span, format!("unknown widget `{}`", item.ident));
+            return;
+        }
+    };
+
+    match (signature.value, item.values.as_slice()) {
+        (Some(kind), [value]) if !kind.matches(value) => error(
+            item.span,
+            format!(
+                "`{}` expects a {} value, found {}",
+                item.ident,
+                kind.name(),
+                value.kind_name()
+            ),
+        ),
+        (Some(kind), []) => error(
+            item.span,
+            format!("`{}` expects a {} value", item.ident, kind.name()),
+        ),
+        (Some(_), values) if values.len() > 1 => error(
+            item.span,
+            format!(
+                "`{}` takes a single value, found {}",
+                item.ident,
+                values.len()
+            ),
+        ),
+        (None, [value, ..]) => error(
+            item.span,
+            format!(
+                "`{}` does not take a value, found {}",
+                item.ident,
+                value.kind_name()
+            ),
+        ),
+        _ => (),
+    }
+
+    for prop in &item.properties.0 {
+        match signature.property_kind(&prop.name) {
+            Some(ValueKind::Keyword(words)) if !ValueKind::Keyword(words).matches(&prop.value) => {
+                error(
+                    prop.span,
+                    format!(
+                        "property `{}` expects one of `{}`, found {}",
+                        prop.name,
+                        words.join("`, `"),
+                        match prop.value {
+                            ast::Value::Ident(ref s) => format!("`{}`", s),
+                            ref value => value.kind_name().to_owned(),
+                        }
+                    ),
+                )
+            }
+            Some(kind) if !kind.matches(&prop.value) => error(
+                prop.span,
+                format!(
+                    "property `{}` expects a {} value, found {}",
+                    prop.name,
+                    kind.name(),
+                    prop.value.kind_name()
+                ),
+            ),
+            Some(_) => (),
+            None => error(
+                prop.span,
+                format!("unknown property `{}` on `{}`", prop.name, item.ident),
+            ),
+        }
+    }
+
+    if let Some(check) = signature.check {
+        for (span, message) in check(item) {
+            error(span, message);
+        }
+    }
+    // Any widget can be placed in a grid
+    for (span, message) in check_cells(item) {
+        error(span, message);
+    }
+
+    if let Some(ref children) = item.children {
+        if signature.children {
+            validate_nodes(registry, sources, children, diagnostics);
+        } else {
+            error(
+                item.span,
+                format!("`{}` does not take a block of children", item.ident),
+            );
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Checks the golden images, like `grui golden golden` does.
+
+use grui::headless::{golden_cases, run_golden_case, DEFAULT_TOLERANCE};
+
+use std::path::Path;
+
+#[test]
+fn golden_images_match() {
+    // Relative to the package directory, which tests run in, as file names show up in the
+    // rendered diagnostics
+    let cases = golden_cases(Path::new("golden")).unwrap();
+    assert!(!cases.is_empty());
+
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| {
+            run_golden_case(case, false, DEFAULT_TOLERANCE)
+                .err()
+                .map(|err| format!("{}: {}", case.display(), err))
+        })
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}